@@ -0,0 +1,285 @@
+use chia::{
+    bls::{aggregate_verify, PublicKey, Signature},
+    consensus::gen::make_aggsig_final_message::u64_to_bytes,
+    sha2::Sha256,
+};
+use chia_protocol::{Bytes32, CoinSpend, SpendBundle};
+use chia_wallet_sdk::{driver::SpendContext, types::Condition};
+use clvmr::{run_program, ChiaDialect};
+use hex_literal::hex;
+
+use crate::CliError;
+
+// Bundles are checked locally before they're submitted, so a malformed or under-signed
+// bundle fails here with a clear error instead of being rejected by the mempool.
+
+const MAINNET_GENESIS_CHALLENGE: [u8; 32] =
+    hex!("ccd5bb71183532bff220ba46c268991a3ff07eb358e8255a65c30a2dce0e5fbb");
+const TESTNET11_GENESIS_CHALLENGE: [u8; 32] =
+    hex!("37a90eb5185a9c4439a91ddc98bbadce7b4feba060d50116a067de66bf236615");
+
+// Block cost limit - no valid spend can use more
+pub const MAX_COST: u64 = 11_000_000_000;
+
+// Opcodes of the AGG_SIG conditions that commit to parts of the coin (CHIP-11)
+const AGG_SIG_PARENT: u8 = 43;
+const AGG_SIG_PUZZLE: u8 = 44;
+const AGG_SIG_AMOUNT: u8 = 45;
+const AGG_SIG_PUZZLE_AMOUNT: u8 = 46;
+const AGG_SIG_PARENT_AMOUNT: u8 = 47;
+const AGG_SIG_PARENT_PUZZLE: u8 = 48;
+
+/// Network-specific data that signatures commit to, so a bundle signed for one network is
+/// invalid on every other one.
+#[derive(Debug, Clone, Copy)]
+pub struct NetworkConstants {
+    /// Appended (after the coin id) to AGG_SIG_ME messages
+    pub genesis_challenge: Bytes32,
+}
+
+impl NetworkConstants {
+    pub fn mainnet() -> Self {
+        Self {
+            genesis_challenge: Bytes32::new(MAINNET_GENESIS_CHALLENGE),
+        }
+    }
+
+    pub fn testnet11() -> Self {
+        Self {
+            genesis_challenge: Bytes32::new(TESTNET11_GENESIS_CHALLENGE),
+        }
+    }
+
+    /// Constants for `--testnet11` (or mainnet), unless a custom genesis challenge is given.
+    pub fn select(testnet11: bool, genesis_challenge: Option<Bytes32>) -> Self {
+        match genesis_challenge {
+            Some(genesis_challenge) => Self { genesis_challenge },
+            None if testnet11 => Self::testnet11(),
+            None => Self::mainnet(),
+        }
+    }
+
+    /// Appended to the messages of the CHIP-11 AGG_SIG conditions: the hash of the genesis
+    /// challenge followed by the condition's opcode.
+    fn additional_data(&self, opcode: u8) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.genesis_challenge);
+        hasher.update([opcode]);
+        hasher.finalize()
+    }
+}
+
+fn final_message(message: &[u8], coin_parts: &[&[u8]], suffix: &[u8]) -> Vec<u8> {
+    let mut final_message = message.to_vec();
+    for part in coin_parts {
+        final_message.extend_from_slice(part);
+    }
+    final_message.extend_from_slice(suffix);
+    final_message
+}
+
+/// Runs every spend and collects the (public key, final message) pairs its AGG_SIG conditions
+/// require. Besides AGG_SIG_ME and AGG_SIG_UNSAFE, this covers the conditions that commit to
+/// the coin's parent, puzzle hash and/or amount, which non-standard wallets may use to
+/// authorize a message spend.
+pub fn required_signatures(
+    coin_spends: &[CoinSpend],
+    network: NetworkConstants,
+) -> Result<Vec<(PublicKey, Vec<u8>)>, CliError> {
+    let mut ctx = SpendContext::new();
+    let mut required = Vec::new();
+    for coin_spend in coin_spends {
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+        let solution = ctx.alloc(&coin_spend.solution)?;
+        let output = run_program(&mut ctx, &ChiaDialect::new(0), puzzle, solution, MAX_COST)
+            .map_err(|_| {
+                CliError::InvalidSpendBundle(format!(
+                    "spend of coin 0x{} fails",
+                    hex::encode(coin_spend.coin.coin_id().to_vec())
+                ))
+            })?
+            .1;
+
+        let coin = &coin_spend.coin;
+        let parent_id: &[u8] = &coin.parent_coin_info.to_vec();
+        let puzzle_hash: &[u8] = &coin.puzzle_hash.to_vec();
+        let amount: &[u8] = &u64_to_bytes(coin.amount);
+        for condition in ctx.extract::<Vec<Condition>>(output)? {
+            let (public_key, message) = match condition {
+                Condition::AggSigUnsafe(agg_sig) => (agg_sig.public_key, agg_sig.message.to_vec()),
+                Condition::AggSigMe(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[&coin.coin_id().to_vec()],
+                        &network.genesis_challenge.to_vec(),
+                    ),
+                ),
+                Condition::AggSigParent(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[parent_id],
+                        &network.additional_data(AGG_SIG_PARENT),
+                    ),
+                ),
+                Condition::AggSigPuzzle(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[puzzle_hash],
+                        &network.additional_data(AGG_SIG_PUZZLE),
+                    ),
+                ),
+                Condition::AggSigAmount(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[amount],
+                        &network.additional_data(AGG_SIG_AMOUNT),
+                    ),
+                ),
+                Condition::AggSigPuzzleAmount(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[puzzle_hash, amount],
+                        &network.additional_data(AGG_SIG_PUZZLE_AMOUNT),
+                    ),
+                ),
+                Condition::AggSigParentAmount(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[parent_id, amount],
+                        &network.additional_data(AGG_SIG_PARENT_AMOUNT),
+                    ),
+                ),
+                Condition::AggSigParentPuzzle(agg_sig) => (
+                    agg_sig.public_key,
+                    final_message(
+                        &agg_sig.message,
+                        &[parent_id, puzzle_hash],
+                        &network.additional_data(AGG_SIG_PARENT_PUZZLE),
+                    ),
+                ),
+                _ => continue,
+            };
+            required.push((public_key, message));
+        }
+    }
+
+    Ok(required)
+}
+
+/// Assembles `coin_spends` into a spend bundle, checking that `aggregated_signature` covers
+/// exactly the signatures the spends require on `network`.
+pub fn assemble_and_verify(
+    coin_spends: Vec<CoinSpend>,
+    aggregated_signature: Signature,
+    network: NetworkConstants,
+) -> Result<SpendBundle, CliError> {
+    let required = required_signatures(&coin_spends, network)?;
+    if !aggregate_verify(
+        &aggregated_signature,
+        required
+            .iter()
+            .map(|(public_key, message)| (public_key, message.as_slice())),
+    ) {
+        return Err(CliError::InvalidSpendBundle(
+            "aggregated signature does not match the required signatures".to_string(),
+        ));
+    }
+
+    Ok(SpendBundle::new(coin_spends, aggregated_signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use chia::{
+        bls::{sign, SecretKey},
+        puzzles::{standard::StandardArgs, LineageProof},
+    };
+    use chia_protocol::Coin;
+    use chia_wallet_sdk::driver::{StreamedCat, StreamingPuzzleInfo};
+
+    use super::*;
+    use crate::{local_signer::build_message_spend, message_spend::MessageSpend};
+
+    fn message_spend(network: NetworkConstants) -> MessageSpend {
+        let secret_key = SecretKey::from_seed(&[1; 32]);
+        let p2_puzzle_hash = StandardArgs::curry_tree_hash(secret_key.public_key()).into();
+        let streamed_coin = StreamedCat::new(
+            Coin::new(Bytes32::new([2; 32]), Bytes32::new([3; 32]), 1_000),
+            Bytes32::new([4; 32]),
+            LineageProof {
+                parent_parent_coin_info: Bytes32::new([5; 32]),
+                parent_inner_puzzle_hash: Bytes32::new([6; 32]),
+                parent_amount: 1_000,
+            },
+            StreamingPuzzleInfo::new(p2_puzzle_hash, None, 2_000, 1_000),
+        );
+        let coin = Coin::new(Bytes32::new([7; 32]), p2_puzzle_hash, 500);
+
+        build_message_spend(&streamed_coin, &secret_key, coin, 100, 1_500, network).unwrap()
+    }
+
+    #[test]
+    fn valid_bundle_is_assembled() {
+        let network = NetworkConstants::mainnet();
+        let spend = message_spend(network);
+
+        let bundle = assemble_and_verify(
+            spend.coin_spends.clone(),
+            spend.aggregated_signature.clone(),
+            network,
+        )
+        .unwrap();
+        assert_eq!(bundle.coin_spends, spend.coin_spends);
+        assert_eq!(bundle.aggregated_signature, spend.aggregated_signature);
+    }
+
+    #[test]
+    fn mangled_signature_is_rejected() {
+        let network = NetworkConstants::mainnet();
+        let spend = message_spend(network);
+        let other_key = SecretKey::from_seed(&[8; 32]);
+
+        for signature in [
+            Signature::default(),
+            spend.aggregated_signature.clone() + &sign(&other_key, b"extra"),
+            sign(&other_key, b"unrelated"),
+        ] {
+            assert!(matches!(
+                assemble_and_verify(spend.coin_spends.clone(), signature, network),
+                Err(CliError::InvalidSpendBundle(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn bundle_is_only_valid_on_its_network() {
+        let custom = NetworkConstants::select(false, Some(Bytes32::new([9; 32])));
+        let networks = [
+            NetworkConstants::mainnet(),
+            NetworkConstants::testnet11(),
+            custom,
+        ];
+
+        for signed_for in networks {
+            let spend = message_spend(signed_for);
+            for verified_on in networks {
+                let result = assemble_and_verify(
+                    spend.coin_spends.clone(),
+                    spend.aggregated_signature.clone(),
+                    verified_on,
+                );
+                if verified_on.genesis_challenge == signed_for.genesis_challenge {
+                    assert!(result.is_ok());
+                } else {
+                    assert!(matches!(result, Err(CliError::InvalidSpendBundle(_))));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use chia_protocol::Bytes32;
+use serde::Deserialize;
+
+use crate::CliError;
+
+/// Display information for a CAT. The on-chain amount is always in mojos; `decimals` only
+/// changes how it's shown.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CatMetadata {
+    pub ticker: String,
+    pub decimals: u32,
+}
+
+impl Default for CatMetadata {
+    fn default() -> Self {
+        Self {
+            ticker: "CAT".to_string(),
+            decimals: 3,
+        }
+    }
+}
+
+impl CatMetadata {
+    pub fn format(&self, mojos: u64) -> String {
+        format!(
+            "{:.*} {}",
+            self.decimals as usize,
+            mojos as f64 / 10f64.powi(self.decimals as i32),
+            self.ticker
+        )
+    }
+}
+
+/// Contents of a `--cat-metadata` file: a JSON object mapping asset ids (hex) to
+/// `{"ticker": "...", "decimals": 3}`. Unknown assets fall back to "CAT" with 3 decimals.
+#[derive(Debug, Default)]
+pub struct CatMetadataMap(HashMap<Bytes32, CatMetadata>);
+
+impl CatMetadataMap {
+    pub fn load(path: Option<&str>) -> Result<Self, CliError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let bytes = std::fs::read(path).map_err(CliError::ReadFile)?;
+        let entries: HashMap<String, CatMetadata> =
+            serde_json::from_slice(&bytes).map_err(|_| CliError::InvalidCatMetadata)?;
+
+        entries
+            .into_iter()
+            .map(|(asset_id, metadata)| {
+                let asset_id: [u8; 32] =
+                    hex::decode(asset_id.strip_prefix("0x").unwrap_or(&asset_id))
+                        .ok()
+                        .and_then(|asset_id| asset_id.try_into().ok())
+                        .ok_or(CliError::InvalidCatMetadata)?;
+                Ok((Bytes32::new(asset_id), metadata))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn get(&self, asset_id: Bytes32) -> CatMetadata {
+        self.0.get(&asset_id).cloned().unwrap_or_default()
+    }
+
+    /// Resolves a CAT given on the command line: a hex asset id, or otherwise a ticker from this
+    /// map (case-insensitive).
+    pub fn resolve_asset_id(&self, cat: &str) -> Result<Bytes32, CliError> {
+        if let Some(asset_id) = hex::decode(cat.strip_prefix("0x").unwrap_or(cat))
+            .ok()
+            .and_then(|asset_id| <[u8; 32]>::try_from(asset_id).ok())
+        {
+            return Ok(Bytes32::new(asset_id));
+        }
+
+        let mut matches = self
+            .0
+            .iter()
+            .filter(|(_, metadata)| metadata.ticker.eq_ignore_ascii_case(cat))
+            .map(|(asset_id, _)| *asset_id);
+        match (matches.next(), matches.next()) {
+            (Some(asset_id), None) => Ok(asset_id),
+            (Some(_), Some(_)) => Err(CliError::AmbiguousTicker(cat.to_string())),
+            (None, _) => Err(CliError::UnknownCat(cat.to_string())),
+        }
+    }
+}
@@ -1,9 +1,13 @@
+use std::time::Duration;
+
 use dirs::data_dir;
-use reqwest::Identity;
+use reqwest::{Identity, StatusCode};
 use sage_api::{
-    GetDerivations, GetDerivationsResponse, SendCat, SendCatResponse, SendXch, SignCoinSpends,
-    SignCoinSpendsResponse,
+    Amount, GetCat, GetCatResponse, GetDerivations, GetDerivationsResponse, GetKey, GetKeyResponse,
+    GetSyncStatus, GetSyncStatusResponse, MultiSend, SendCat, SendCatResponse, SendXch,
+    SignCoinSpends, SignCoinSpendsResponse, SubmitTransaction, SubmitTransactionResponse,
 };
+use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,8 +18,65 @@ pub enum ClientError {
     RequestError(#[from] reqwest::Error),
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("Wallet unavailable: {0}")]
+    Unavailable(String),
 }
 
+impl ClientError {
+    /// Whether the request can safely be retried - i.e., it never reached Sage (connection
+    /// failure) or Sage explicitly refused to handle it for now.
+    fn is_transient(&self) -> bool {
+        match self {
+            ClientError::RequestError(err) => err.is_connect(),
+            ClientError::Unavailable(_) => true,
+            ClientError::CertificateError | ClientError::InvalidResponse(_) => false,
+        }
+    }
+}
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+// Sage reports a busy database, an ongoing sync or a locked wallet as a regular error response.
+// Requests failing with one of these never got to act, so they're retried like a 503.
+const TRANSIENT_SAGE_ERRORS: [&str; 6] = [
+    "database is locked",
+    "busy",
+    "syncing",
+    "not synced",
+    "wallet is locked",
+    "not logged in",
+];
+
+/// Whether a non-2xx response from Sage means it's temporarily unable to handle requests
+/// (as opposed to rejecting this one).
+fn is_transient_response(status: StatusCode, body: &str) -> bool {
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::SERVICE_UNAVAILABLE
+        || status == StatusCode::LOCKED
+    {
+        return true;
+    }
+
+    let body = body.to_lowercase();
+    TRANSIENT_SAGE_ERRORS
+        .iter()
+        .any(|error| body.contains(error))
+}
+
+/// Sage may encode amounts as JSON numbers or (for values that don't fit in a JS number) as
+/// strings - this is the only place that unpacks them.
+pub fn amount_to_u64(amount: &Amount) -> Result<u64, ClientError> {
+    match amount {
+        Amount::Number(amount) => Ok(*amount),
+        Amount::String(amount) => amount
+            .parse::<u64>()
+            .map_err(|_| ClientError::InvalidResponse(format!("Invalid amount: {:?}", amount))),
+    }
+}
+
+/// Client for the Sage wallet RPC. Cheap to clone - clones share the same connection pool.
+#[derive(Clone)]
 pub struct SageClient {
     client: reqwest::Client,
     base_url: String,
@@ -46,73 +107,161 @@ impl SageClient {
     }
 
     pub async fn send_cat(&self, request: SendCat) -> Result<SendCatResponse, ClientError> {
-        let url = format!("{}/send_cat", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if !response.status().is_success() {
-            return Err(ClientError::InvalidResponse(format!(
-                "Status: {}, Body: {:?}",
-                response.status(),
-                response.text().await?
-            )));
-        }
-
-        let response_body = response.json::<SendCatResponse>().await?;
-        Ok(response_body)
+        self.post("send_cat", &request).await
     }
 
     pub async fn get_derivations(
         &self,
         request: GetDerivations,
     ) -> Result<GetDerivationsResponse, ClientError> {
-        let url = format!("{}/get_derivations", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if !response.status().is_success() {
-            return Err(ClientError::InvalidResponse(format!(
-                "Status: {}, Body: {:?}",
-                response.status(),
-                response.text().await?
-            )));
-        }
+        self.post("get_derivations", &request).await
+    }
 
-        let response_body = response.json::<GetDerivationsResponse>().await?;
-        Ok(response_body)
+    pub async fn get_key(&self, request: GetKey) -> Result<GetKeyResponse, ClientError> {
+        self.post("get_key", &request).await
+    }
+
+    pub async fn get_cat(&self, request: GetCat) -> Result<GetCatResponse, ClientError> {
+        self.post("get_cat", &request).await
+    }
+
+    pub async fn get_sync_status(
+        &self,
+        request: GetSyncStatus,
+    ) -> Result<GetSyncStatusResponse, ClientError> {
+        self.post("get_sync_status", &request).await
     }
 
     pub async fn send_xch(&self, request: SendXch) -> Result<SendCatResponse, ClientError> {
-        let url = format!("{}/send_xch", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if !response.status().is_success() {
-            return Err(ClientError::InvalidResponse(format!(
-                "Status: {}, Body: {:?}",
-                response.status(),
-                response.text().await?
-            )));
-        }
+        self.post("send_xch", &request).await
+    }
 
-        let response_body = response.json::<SendCatResponse>().await?;
-        Ok(response_body)
+    pub async fn multi_send(&self, request: MultiSend) -> Result<SendCatResponse, ClientError> {
+        self.post("multi_send", &request).await
     }
 
     pub async fn sign_coin_spends(
         &self,
         request: SignCoinSpends,
     ) -> Result<SignCoinSpendsResponse, ClientError> {
-        let url = format!("{}/sign_coin_spends", self.base_url);
+        self.post("sign_coin_spends", &request).await
+    }
+
+    pub async fn submit_transaction(
+        &self,
+        request: SubmitTransaction,
+    ) -> Result<SubmitTransactionResponse, ClientError> {
+        self.post("submit_transaction", &request).await
+    }
 
-        let response = self.client.post(&url).json(&request).send().await?;
+    /// Sends a request, retrying with exponential backoff while Sage is unreachable or busy.
+    /// Only failures where Sage cannot have acted on the request are retried, since
+    /// `send_*` and `sign_coin_spends` may submit transactions.
+    async fn post<Req: Serialize, Res: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        request: &Req,
+    ) -> Result<Res, ClientError> {
+        let url = format!("{}/{}", self.base_url, endpoint);
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+
+        loop {
+            match self.post_once(&url, request).await {
+                Err(err) if attempt < MAX_ATTEMPTS && err.is_transient() => {
+                    eprintln!(
+                        "Sage request to {} failed ({}) - retrying in {:?}...",
+                        endpoint, err, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn post_once<Req: Serialize, Res: DeserializeOwned>(
+        &self,
+        url: &str,
+        request: &Req,
+    ) -> Result<Res, ClientError> {
+        let response = self.client.post(url).json(request).send().await?;
 
-        if !response.status().is_success() {
-            return Err(ClientError::InvalidResponse(format!(
-                "Status: {}, Body: {:?}",
-                response.status(),
-                response.text().await?
-            )));
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            let message = format!("Status: {}, Body: {:?}", status, body);
+            return Err(if is_transient_response(status, &body) {
+                ClientError::Unavailable(message)
+            } else {
+                ClientError::InvalidResponse(message)
+            });
         }
 
-        let response_body = response.json::<SignCoinSpendsResponse>().await?;
+        let response_body = response.json::<Res>().await?;
         Ok(response_body)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_and_unavailable_statuses_are_transient() {
+        assert!(is_transient_response(StatusCode::TOO_MANY_REQUESTS, ""));
+        assert!(is_transient_response(StatusCode::SERVICE_UNAVAILABLE, ""));
+        assert!(is_transient_response(StatusCode::LOCKED, ""));
+    }
+
+    #[test]
+    fn sage_busy_syncing_and_locked_errors_are_transient() {
+        assert!(is_transient_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Database error: database is locked"
+        ));
+        assert!(is_transient_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Wallet is Syncing, try again later"
+        ));
+        assert!(is_transient_response(
+            StatusCode::UNAUTHORIZED,
+            "Wallet is locked"
+        ));
+        assert!(is_transient_response(
+            StatusCode::UNAUTHORIZED,
+            "Not logged in"
+        ));
+    }
+
+    #[test]
+    fn other_errors_are_not_transient() {
+        assert!(!is_transient_response(
+            StatusCode::BAD_REQUEST,
+            "Insufficient balance"
+        ));
+        assert!(!is_transient_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Invalid address"
+        ));
+        assert!(!is_transient_response(StatusCode::NOT_FOUND, ""));
+    }
+
+    #[test]
+    fn amount_to_u64_rejects_bogus_amounts() {
+        assert_eq!(amount_to_u64(&Amount::Number(5)).unwrap(), 5);
+        assert_eq!(
+            amount_to_u64(&Amount::String("18446744073709551615".to_string())).unwrap(),
+            u64::MAX
+        );
+
+        for amount in ["", "abc", "-1", "1.5", "18446744073709551616"] {
+            assert!(matches!(
+                amount_to_u64(&Amount::String(amount.to_string())),
+                Err(ClientError::InvalidResponse(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use dirs::cache_dir;
+use serde::{Deserialize, Serialize};
+
+// Derivations never change for a given key, so the address -> public key mapping is cached
+// per wallet fingerprint and network (and per derivation kind). `scanned` records how many
+// derivations have already been enumerated, so a cache miss resumes the search instead of
+// starting over. Addresses are network-specific, so a scan on one network says nothing about
+// the other - hence the network (address prefix) is part of the cache file name.
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CachedDerivations {
+    pub scanned: u64,
+    pub public_keys: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    unhardened: CachedDerivations,
+    hardened: CachedDerivations,
+}
+
+pub struct DerivationCache {
+    path: Option<PathBuf>,
+    file: CacheFile,
+}
+
+impl DerivationCache {
+    /// Loads the cache for `fingerprint` on the network with address `prefix`. A missing or
+    /// unreadable cache file yields an empty cache - it will simply be rebuilt from Sage.
+    pub fn load(fingerprint: u32, prefix: &str) -> Self {
+        Self::load_in(cache_dir(), fingerprint, prefix)
+    }
+
+    fn load_in(dir: Option<PathBuf>, fingerprint: u32, prefix: &str) -> Self {
+        let path = dir.map(|dir| {
+            dir.join("streaming")
+                .join(format!("derivations-{}-{}.json", fingerprint, prefix))
+        });
+        let file = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        Self { path, file }
+    }
+
+    pub fn clear(&mut self) {
+        self.file = CacheFile::default();
+    }
+
+    pub fn derivations(&mut self, hardened: bool) -> &mut CachedDerivations {
+        if hardened {
+            &mut self.file.hardened
+        } else {
+            &mut self.file.unhardened
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(&self.file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_is_per_network() {
+        let dir = std::env::temp_dir().join(format!("streaming-test-{}", std::process::id()));
+
+        let mut cache = DerivationCache::load_in(Some(dir.clone()), 1234, "xch");
+        let derivations = cache.derivations(false);
+        derivations.scanned = 10_000;
+        derivations
+            .public_keys
+            .insert("xch1address".to_string(), "b0".to_string());
+        cache.save().unwrap();
+
+        // A full mainnet scan must not make a testnet lookup skip querying Sage
+        let mut testnet = DerivationCache::load_in(Some(dir.clone()), 1234, "txch");
+        assert_eq!(testnet.derivations(false).scanned, 0);
+        assert!(testnet.derivations(false).public_keys.is_empty());
+
+        let mut mainnet = DerivationCache::load_in(Some(dir.clone()), 1234, "xch");
+        assert_eq!(mainnet.derivations(false).scanned, 10_000);
+        assert_eq!(
+            mainnet.derivations(false).public_keys.get("xch1address"),
+            Some(&"b0".to_string())
+        );
+        assert_eq!(mainnet.derivations(true).scanned, 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
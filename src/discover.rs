@@ -0,0 +1,213 @@
+use std::{fs, io, path::PathBuf};
+
+use chia_protocol::Bytes32;
+use chia_wallet_sdk::{
+    coinset::{ChiaRpcClient, CoinRecord, CoinsetClient},
+    driver::StreamingPuzzleInfo,
+};
+use dirs::cache_dir;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use streaming::stream_id::StreamId;
+
+use crate::CliError;
+
+// Hint lookups return every matching coin at once, so we query in block-height windows
+// to keep individual responses bounded for busy addresses
+const DISCOVER_PAGE_SIZE: u32 = 100_000;
+
+// Progress of a discover run, saved after every page so an interrupted run can resume where
+// it stopped. The page at the cursor is always fetched again (new coins may have been created
+// at the tip since), and coins that were unspent are re-checked, since a final claim or a
+// clawback spends the latest coin without creating a new hinted one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DiscoverProgress {
+    start_height: u32,
+    cursor: u32,
+    coin_records: Vec<CoinRecord>,
+}
+
+impl DiscoverProgress {
+    fn path(recipient: Bytes32, stream_prefix: &str) -> Option<PathBuf> {
+        cache_dir().map(|dir| {
+            dir.join("streaming").join(format!(
+                "discover-{}-{}.json",
+                stream_prefix,
+                hex::encode(recipient.to_vec())
+            ))
+        })
+    }
+
+    fn load(path: Option<&PathBuf>, start_height: u32) -> Self {
+        path.and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .filter(|progress| progress.start_height == start_height)
+            .unwrap_or(Self {
+                start_height,
+                cursor: start_height,
+                coin_records: Vec::new(),
+            })
+    }
+
+    fn save(&self, path: Option<&PathBuf>) -> io::Result<()> {
+        let Some(path) = path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscoveredStream {
+    pub stream_id: String,
+    pub finished: bool,
+    pub spends: u32,
+    // Only set for active streams
+    pub latest_coin_id: Option<String>,
+    pub remaining_amount: Option<u64>,
+}
+
+pub async fn discover_streams(
+    cli: &CoinsetClient,
+    recipient: Bytes32,
+    stream_prefix: &str,
+    include_finished: bool,
+    start_height: u32,
+    resume: bool,
+    max_blocks: Option<u32>,
+) -> Result<Vec<DiscoveredStream>, CliError> {
+    let state_resp = cli
+        .get_blockchain_state()
+        .await
+        .map_err(CliError::Reqwest)?;
+    let Some(state) = state_resp.blockchain_state else {
+        return Err(CliError::BlockchainStateUnavailable);
+    };
+    let peak_height = state.peak.height;
+    if let Some(max_blocks) = max_blocks {
+        if peak_height.saturating_sub(start_height) > max_blocks {
+            return Err(CliError::DiscoverRangeTooLarge(max_blocks));
+        }
+    }
+
+    // Both the launch coin and every continuation coin carry the stream hint
+    let hint = StreamingPuzzleInfo::get_hint(recipient);
+    let mut coin_records: IndexMap<Bytes32, CoinRecord> = IndexMap::new();
+
+    let progress_path = DiscoverProgress::path(recipient, stream_prefix);
+    let mut progress =
+        DiscoverProgress::load(progress_path.as_ref().filter(|_| resume), start_height);
+    for coin_record in progress.coin_records.drain(..) {
+        let coin_id = coin_record.coin.coin_id();
+        let coin_record = if coin_record.spent {
+            coin_record
+        } else {
+            let resp = cli
+                .get_coin_record_by_name(coin_id)
+                .await
+                .map_err(CliError::Reqwest)?;
+            resp.coin_record.unwrap_or(coin_record)
+        };
+        coin_records.insert(coin_id, coin_record);
+    }
+    if progress.cursor > start_height {
+        eprintln!("Resuming from block height {}...", progress.cursor);
+    }
+
+    // The launch coin of a stream created before `start_height` is never seen, so such a
+    // stream is either missed or reported under its first coin after `start_height`
+    let mut start_height = progress.cursor;
+    while start_height <= peak_height {
+        let end_height = start_height.saturating_add(DISCOVER_PAGE_SIZE);
+        let resp = cli
+            .get_coin_records_by_hint(hint, Some(start_height), Some(end_height), Some(true))
+            .await
+            .map_err(CliError::Reqwest)?;
+        if !resp.success {
+            return Err(CliError::CoinsetUnsuccessful(
+                resp.error.unwrap_or_default(),
+            ));
+        }
+
+        for coin_record in resp.coin_records.unwrap_or_default() {
+            coin_records.insert(coin_record.coin.coin_id(), coin_record);
+        }
+
+        // Resume from this page next time - it's the tail if this is the last one
+        progress.cursor = start_height;
+        progress.coin_records = coin_records.values().cloned().collect();
+        if let Err(err) = progress.save(progress_path.as_ref()) {
+            eprintln!("Warning: could not save discover progress: {}", err);
+        }
+
+        start_height = end_height;
+    }
+
+    // Coins whose parent is not part of the set are launch coins (i.e., stream ids); every
+    // other coin is a continuation, so following children gives the latest coin of each stream
+    let children: IndexMap<Bytes32, Bytes32> = coin_records
+        .iter()
+        .filter(|(_, record)| coin_records.contains_key(&record.coin.parent_coin_info))
+        .map(|(coin_id, record)| (record.coin.parent_coin_info, *coin_id))
+        .collect();
+
+    let mut streams = Vec::new();
+    for (launch_coin_id, launch_record) in coin_records
+        .iter()
+        .filter(|(_, record)| !coin_records.contains_key(&record.coin.parent_coin_info))
+    {
+        let mut latest_record = launch_record;
+        let mut spends = 0;
+        while let Some(child_id) = children.get(&latest_record.coin.coin_id()) {
+            latest_record = &coin_records[child_id];
+            spends += 1;
+        }
+
+        let finished = latest_record.spent;
+        if finished && !include_finished {
+            continue;
+        }
+
+        streams.push(DiscoveredStream {
+            stream_id: StreamId(*launch_coin_id).encode(stream_prefix)?,
+            finished,
+            spends,
+            latest_coin_id: (!finished).then(|| hex::encode(latest_record.coin.coin_id().to_vec())),
+            remaining_amount: (!finished).then_some(latest_record.coin.amount),
+        });
+    }
+
+    Ok(streams)
+}
+
+pub fn print_discovered_streams(streams: &[DiscoveredStream]) {
+    for stream in streams {
+        println!("Stream {}", stream.stream_id);
+        println!(
+            "  Status: {}",
+            if stream.finished {
+                "finished (fully claimed or clawed back)"
+            } else {
+                "active"
+            }
+        );
+        println!("  Spends: {}", stream.spends);
+        if let (Some(latest_coin_id), Some(remaining_amount)) =
+            (&stream.latest_coin_id, stream.remaining_amount)
+        {
+            println!("  Latest coin: {}", latest_coin_id);
+            // The asset (and so its decimals) isn't known without parsing the launch spend -
+            // `view` shows the amount with the CAT's ticker and decimals
+            println!("  Remaining (unclaimed) amount: {} mojos", remaining_amount);
+        }
+    }
+
+    if streams.is_empty() {
+        eprintln!("No streams found.");
+    }
+}
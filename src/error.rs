@@ -0,0 +1,26 @@
+use chia_wallet_sdk::driver::DriverError;
+use thiserror::Error;
+
+/// Errors produced by the stream encoding, parsing and validation helpers (as opposed to
+/// `CliError`, which also covers the network and wallet side of the CLI).
+#[derive(Error, Debug)]
+pub enum StreamingError {
+    #[error("Invalid stream id")]
+    InvalidStreamId,
+    #[error("Invalid stream id: expected a 32-byte coin id, got {0} bytes")]
+    InvalidStreamIdLength(usize),
+    #[error("Invalid state token")]
+    InvalidStateToken,
+    #[error("State token does not match its coin's puzzle hash")]
+    StateTokenPuzzleHashMismatch,
+    #[error("Invalid launch hints: {0}")]
+    InvalidLaunchHints(String),
+    #[error("Invalid time window: {0}")]
+    TimeWindowInvalid(String),
+    #[error("Failed to encode address")]
+    Bech32(#[from] bech32::Error),
+    #[error("Driver error")]
+    Driver(#[from] DriverError),
+}
+
+pub type Result<T> = std::result::Result<T, StreamingError>;
@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use chia_protocol::Bytes32;
+
+use crate::{decode_address, parse_amount, CliError};
+
+// `launch-batch` files have one stream per line: `recipient,amount,start,end,clawback`, where
+// the amount uses the same format as `launch` and the clawback address can be 'none'. Empty
+// lines, lines starting with '#' and a leading `recipient,...` header are ignored.
+
+pub struct BatchStream {
+    pub recipient: String,
+    pub recipient_puzzle_hash: Bytes32,
+    pub amount: u64,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+    pub clawback_ph: Option<Bytes32>,
+}
+
+fn parse_line(line: &str, testnet11: bool) -> Result<BatchStream, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [recipient, amount, start, end, clawback] = fields[..] else {
+        return Err(format!("expected 5 columns, found {}", fields.len()));
+    };
+
+    Ok(BatchStream {
+        recipient: recipient.to_string(),
+        recipient_puzzle_hash: decode_address(recipient, testnet11)
+            .map_err(|err| err.to_string())?,
+        amount: parse_amount(amount.to_string(), true).map_err(|err| err.to_string())?,
+        start_timestamp: start
+            .parse()
+            .map_err(|_| "invalid start time".to_string())?,
+        end_timestamp: end.parse().map_err(|_| "invalid end time".to_string())?,
+        clawback_ph: if clawback == "none" {
+            None
+        } else {
+            Some(decode_address(clawback, testnet11).map_err(|err| err.to_string())?)
+        },
+    })
+}
+
+/// Reads a batch file. Streams with identical parameters are rejected unless `allow_duplicates`
+/// is set - funded from the same coin, they would create the same coin twice, which makes the
+/// whole transaction invalid. Streams that only differ in their clawback address are a likely
+/// copy-paste mistake too, so they're warned about.
+pub fn read_batch_file(
+    path: &str,
+    testnet11: bool,
+    allow_duplicates: bool,
+) -> Result<Vec<BatchStream>, CliError> {
+    let contents = std::fs::read_to_string(path).map_err(CliError::ReadFile)?;
+
+    let mut streams = Vec::new();
+    let mut seen = HashSet::new();
+    let mut seen_without_clawback = HashSet::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("recipient,") {
+            continue;
+        }
+
+        let stream = parse_line(line, testnet11).map_err(|reason| {
+            CliError::InvalidBatchFile(format!("line {}: {}", index + 1, reason))
+        })?;
+        let key = (
+            stream.recipient_puzzle_hash,
+            stream.amount,
+            stream.start_timestamp,
+            stream.end_timestamp,
+        );
+        if !seen.insert((key, stream.clawback_ph)) {
+            if !allow_duplicates {
+                return Err(CliError::InvalidBatchFile(format!(
+                    "line {}: duplicate stream (use --allow-duplicates to launch it anyway)",
+                    index + 1
+                )));
+            }
+            eprintln!("Warning: line {}: duplicate stream", index + 1);
+        } else if !seen_without_clawback.insert(key) {
+            eprintln!(
+                "Warning: line {}: same recipient, amount and times as an earlier stream, with a different clawback address",
+                index + 1
+            );
+        }
+        streams.push(stream);
+    }
+
+    if streams.is_empty() {
+        return Err(CliError::InvalidBatchFile("no streams".to_string()));
+    }
+
+    Ok(streams)
+}
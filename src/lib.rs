@@ -0,0 +1,14 @@
+//! Helpers for streamed CATs that don't need a network connection or a wallet: stream id and
+//! state token encoding, vesting math, spend parsing and stream timelines. The CLI (Sage and
+//! coinset.org integration) is only built with the `cli` feature.
+//!
+//! `prelude` re-exports the public API, including the puzzle types from chia-wallet-sdk.
+
+pub mod error;
+pub mod prelude;
+pub mod solution;
+pub mod state_token;
+pub mod stream_id;
+pub mod stream_parameters;
+pub mod timeline;
+pub mod vesting;
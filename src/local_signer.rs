@@ -0,0 +1,195 @@
+use chia::{
+    bls::{
+        master_to_wallet_hardened_intermediate, master_to_wallet_unhardened_intermediate, sign,
+        DerivableKey, SecretKey, Signature,
+    },
+    consensus::gen::make_aggsig_final_message::u64_to_bytes,
+    puzzles::{standard::StandardArgs, DeriveSynthetic},
+};
+use chia_protocol::{Bytes, Bytes32, Coin};
+use chia_wallet_sdk::{
+    coinset::{ChiaRpcClient, CoinsetClient},
+    driver::{SpendContext, StandardLayer, StreamedCat},
+    types::{Conditions, Memos},
+};
+
+use crate::{
+    bundle::{required_signatures, NetworkConstants},
+    message_spend::MessageSpend,
+    CliError,
+};
+
+// Signs claims and clawbacks with a key read from a file instead of Sage. The authorization
+// message has to come from a coin locked by the recipient (or clawback) puzzle hash, so an
+// unspent XCH coin at that address is spent to send it and pay the fee - the change goes
+// back to the same address. The streamed CAT spend itself needs no signature.
+
+/// Reads a master secret key from `path`: either 32 hex-encoded bytes or a mnemonic.
+pub fn load_master_key(path: &str) -> Result<SecretKey, CliError> {
+    let contents = std::fs::read_to_string(path).map_err(CliError::ReadFile)?;
+    let contents = contents.trim();
+
+    if let Ok(bytes) = hex::decode(contents.strip_prefix("0x").unwrap_or(contents)) {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| CliError::InvalidSignerFile)?;
+        return SecretKey::from_bytes(&bytes).map_err(|_| CliError::InvalidSignerFile);
+    }
+
+    let mnemonic =
+        bip39::Mnemonic::parse_normalized(contents).map_err(|_| CliError::InvalidSignerFile)?;
+    Ok(SecretKey::from_seed(&mnemonic.to_seed("")))
+}
+
+/// Derives the synthetic secret key whose standard puzzle hash is `p2_puzzle_hash`, trying
+/// the first `max_derivations` indices of the selected keyspaces.
+pub fn find_secret_key(
+    master_key: &SecretKey,
+    p2_puzzle_hash: Bytes32,
+    max_derivations: u64,
+    hardened: bool,
+    auto: bool,
+) -> Result<SecretKey, CliError> {
+    let unhardened_intermediate = master_to_wallet_unhardened_intermediate(master_key);
+    let hardened_intermediate = master_to_wallet_hardened_intermediate(master_key);
+
+    for index in 0..max_derivations.min(u32::MAX as u64) as u32 {
+        if auto || !hardened {
+            let secret_key = unhardened_intermediate
+                .derive_unhardened(index)
+                .derive_synthetic();
+            if StandardArgs::curry_tree_hash(secret_key.public_key()) == p2_puzzle_hash.into() {
+                eprintln!("Found key at unhardened derivation index {}", index);
+                return Ok(secret_key);
+            }
+        }
+
+        if auto || hardened {
+            let secret_key = hardened_intermediate
+                .derive_hardened(index)
+                .derive_synthetic();
+            if StandardArgs::curry_tree_hash(secret_key.public_key()) == p2_puzzle_hash.into() {
+                eprintln!("Found key at hardened derivation index {}", index);
+                return Ok(secret_key);
+            }
+        }
+    }
+
+    Err(CliError::PublicKeyNotFound(max_derivations, auto))
+}
+
+/// Builds and signs the spend of an XCH coin at `p2_puzzle_hash` that authorizes spending
+/// `streamed_coin` at `payment_time` and pays `fee`.
+pub async fn sign_message_spend(
+    cli: &CoinsetClient,
+    streamed_coin: &StreamedCat,
+    secret_key: &SecretKey,
+    p2_puzzle_hash: Bytes32,
+    fee: u64,
+    payment_time: u64,
+    network: NetworkConstants,
+) -> Result<MessageSpend, CliError> {
+    let resp = cli
+        .get_coin_records_by_puzzle_hash(p2_puzzle_hash, None, None, Some(false))
+        .await
+        .map_err(CliError::Reqwest)?;
+    if !resp.success {
+        return Err(CliError::CoinsetUnsuccessful(
+            resp.error.unwrap_or_default(),
+        ));
+    }
+    let Some(coin) = resp
+        .coin_records
+        .unwrap_or_default()
+        .into_iter()
+        .map(|coin_record| coin_record.coin)
+        .filter(|coin| coin.amount >= fee)
+        .max_by_key(|coin| coin.amount)
+    else {
+        return Err(CliError::NoFeeCoin);
+    };
+
+    build_message_spend(streamed_coin, secret_key, coin, fee, payment_time, network)
+}
+
+/// Spends `coin` (locked by the standard puzzle of `secret_key`) to authorize spending
+/// `streamed_coin` at `payment_time`, paying `fee` and sending the change back to `coin`'s
+/// puzzle hash.
+pub fn build_message_spend(
+    streamed_coin: &StreamedCat,
+    secret_key: &SecretKey,
+    coin: Coin,
+    fee: u64,
+    payment_time: u64,
+    network: NetworkConstants,
+) -> Result<MessageSpend, CliError> {
+    let p2_puzzle_hash = coin.puzzle_hash;
+    let mut ctx = SpendContext::new();
+    let p2 = StandardLayer::new(secret_key.public_key());
+    let coin_id_ptr = ctx.alloc(&streamed_coin.coin.coin_id())?;
+    let mut conditions = Conditions::new()
+        .send_message(
+            23,
+            Bytes::new(u64_to_bytes(payment_time)),
+            vec![coin_id_ptr],
+        )
+        .reserve_fee(fee);
+    if coin.amount > fee {
+        conditions = conditions.create_coin(p2_puzzle_hash, coin.amount - fee, Memos::None);
+    }
+    p2.spend(&mut ctx, coin, conditions)?;
+    let coin_spends = ctx.take();
+
+    // The only signature needed is the AGG_SIG_ME of the standard puzzle spend
+    let mut aggregated_signature = Signature::default();
+    for (_, message) in required_signatures(&coin_spends, network)? {
+        aggregated_signature += &sign(secret_key, message);
+    }
+
+    Ok(MessageSpend {
+        coin_spends,
+        aggregated_signature,
+        payment_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p2_puzzle_hash(master_key: &SecretKey, index: u32, hardened: bool) -> Bytes32 {
+        let secret_key = if hardened {
+            master_to_wallet_hardened_intermediate(master_key).derive_hardened(index)
+        } else {
+            master_to_wallet_unhardened_intermediate(master_key).derive_unhardened(index)
+        };
+        StandardArgs::curry_tree_hash(secret_key.derive_synthetic().public_key()).into()
+    }
+
+    #[test]
+    fn find_secret_key_within_limit() {
+        let master_key = SecretKey::from_seed(&[1; 32]);
+
+        for hardened in [false, true] {
+            let p2_puzzle_hash = p2_puzzle_hash(&master_key, 3, hardened);
+            let secret_key =
+                find_secret_key(&master_key, p2_puzzle_hash, 4, hardened, false).unwrap();
+            assert_eq!(
+                Bytes32::from(StandardArgs::curry_tree_hash(secret_key.public_key())),
+                p2_puzzle_hash
+            );
+            assert!(find_secret_key(&master_key, p2_puzzle_hash, 4, !hardened, true).is_ok());
+        }
+    }
+
+    #[test]
+    fn find_secret_key_reports_limit() {
+        let master_key = SecretKey::from_seed(&[1; 32]);
+        let p2_puzzle_hash = p2_puzzle_hash(&master_key, 3, false);
+
+        for (max_derivations, hardened) in [(3, false), (0, false), (10, true)] {
+            assert!(matches!(
+                find_secret_key(&master_key, p2_puzzle_hash, max_derivations, hardened, false),
+                Err(CliError::PublicKeyNotFound(limit, false)) if limit == max_derivations
+            ));
+        }
+    }
+}
@@ -1,25 +1,73 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::OnceLock,
+};
+
+use bundle::{assemble_and_verify, NetworkConstants};
+use cat_metadata::{CatMetadata, CatMetadataMap};
 use chia::{
-    bls::PublicKey, consensus::gen::make_aggsig_final_message::u64_to_bytes, traits::Streamable,
+    bls::PublicKey,
+    consensus::gen::make_aggsig_final_message::u64_to_bytes,
+    puzzles::{cat::CatArgs, standard::StandardArgs, DeriveSynthetic},
 };
-use chia_protocol::{Bytes, Bytes32, Coin, CoinSpend, Program};
+use chia_protocol::{Bytes, Bytes32, Coin, CoinSpend};
 use chia_wallet_sdk::{
-    coinset::{ChiaRpcClient, CoinsetClient},
+    coinset::{ChiaRpcClient, CoinRecord, CoinsetClient, GetCoinRecordResponse},
     driver::{
-        DriverError, Layer, Puzzle, SpendContext, StandardLayer, StreamPuzzle2ndCurryArgs,
-        StreamedCat, StreamingPuzzleInfo,
+        DriverError, Layer, Puzzle, SpendContext, StandardLayer, StreamedCat, StreamingPuzzleInfo,
+        STREAM_PUZZLE, STREAM_PUZZLE_HASH,
     },
     types::Conditions,
     utils::{Address, AddressError},
 };
-use chrono::{Local, TimeZone};
+use chrono::{DateTime, Local, TimeZone, Utc};
 use clap::{Parser, Subcommand};
-use client::SageClient;
+use client::{amount_to_u64, SageClient};
+use clvm_utils::tree_hash;
+use clvmr::{serde::node_from_bytes, Allocator};
+use derivation_cache::{CachedDerivations, DerivationCache};
+use discover::{discover_streams, print_discovered_streams};
+use launch_batch::read_batch_file;
+use local_signer::{find_secret_key, load_master_key, sign_message_spend};
+use message_spend::{
+    coin_spend_from_json, push_message_spend, read_message_spend, spend_bundle_from_json,
+};
 use sage_api::{
-    Amount, AssetKind, CoinJson, CoinSpendJson, GetDerivations, SendCat, SendXch, SignCoinSpends,
+    Amount, AssetKind, CoinJson, CoinSpendJson, GetCat, GetDerivations, GetKey, GetSyncStatus,
+    MultiSend, Payment, SendCat, SendCatResponse, SendXch, SignCoinSpends, SubmitTransaction,
+    TransactionSummary,
+};
+use serde::Serialize;
+use stream_export::StreamExport;
+use streaming::{
+    error::StreamingError,
+    solution::{extract_solution, parse_launch_diagnostic},
+    state_token::{from_state_token, to_state_token},
+    stream_id::StreamId,
+    stream_parameters::StreamParameters,
+    timeline::{StreamClaim, StreamTimeline},
+    vesting::{
+        amount_to_be_paid, child_after_claim, clawback_return_amount, is_frozen_until_end,
+        payment_time_for_amount, payout_coin, project_per_block, simulate_stream,
+        validate_time_window, AVERAGE_BLOCK_TIME,
+    },
 };
 use thiserror::Error;
+use timeline_json::StreamTimelineJson;
 
+mod bundle;
+mod cat_metadata;
 mod client;
+mod derivation_cache;
+mod discover;
+mod launch_batch;
+mod local_signer;
+mod message_spend;
+#[cfg(feature = "server")]
+mod server;
+mod stream_export;
+mod timeline_json;
 
 #[derive(Debug, Parser)]
 #[command(name = "streaming")]
@@ -27,6 +75,26 @@ mod client;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Base URL of a coinset.org-compatible API, overriding the network's default
+    #[arg(long, global = true)]
+    coinset_url: Option<String>,
+    /// JSON file mapping asset ids to {"ticker", "decimals"}, used to display amounts
+    #[arg(long, global = true)]
+    cat_metadata: Option<String>,
+    /// Genesis challenge (hex) of a custom network, used when signing and verifying spends
+    #[arg(long, global = true)]
+    genesis_challenge: Option<String>,
+    /// Abort if a confirmation prompt gets no answer within this long (e.g., 90s or 5m)
+    #[arg(long, global = true)]
+    confirm_timeout: Option<String>,
+    /// Answer every confirmation prompt with yes, without waiting for input
+    #[arg(
+        long,
+        global = true,
+        default_value_t = false,
+        conflicts_with = "confirm_timeout"
+    )]
+    yes: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -41,28 +109,293 @@ enum Commands {
         clawback_address: String,
         #[arg(long, default_value = "0.0001")]
         fee: String,
+        /// Fee in mojos, instead of --fee
+        #[arg(long, conflicts_with = "fee")]
+        fee_mojos: Option<u64>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        /// Don't ask for confirmation when the clawback address is the recipient address
+        #[arg(long, default_value_t = false)]
+        allow_clawback_to_recipient: bool,
+        /// Don't ask for confirmation when a stream ends before the latest block
+        #[arg(long, default_value_t = false)]
+        allow_past_end: bool,
+        /// Return right after submitting instead of waiting for the transaction to confirm
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
+        /// Don't check that the wallet holds enough CAT and XCH before launching
+        #[arg(long, default_value_t = false)]
+        skip_balance_check: bool,
+    },
+
+    /// Launches several streams of the same CAT in one transaction
+    #[command(arg_required_else_help = true)]
+    LaunchBatch {
+        asset_id: String,
+        /// CSV file with one stream per line: recipient,amount,start,end,clawback
+        #[arg(long)]
+        file: String,
+        /// Expected sum of the stream amounts - the launch is aborted if it doesn't match
+        #[arg(long)]
+        total: Option<String>,
+        #[arg(long, default_value = "0.0001")]
+        fee: String,
+        /// Fee in mojos, instead of --fee
+        #[arg(long, conflicts_with = "fee")]
+        fee_mojos: Option<u64>,
         #[arg(long, default_value_t = false)]
         testnet11: bool,
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        /// Don't ask for confirmation when the clawback address is the recipient address
+        #[arg(long, default_value_t = false)]
+        allow_clawback_to_recipient: bool,
+        /// Don't ask for confirmation when a stream ends before the latest block
+        #[arg(long, default_value_t = false)]
+        allow_past_end: bool,
+        /// Return right after submitting instead of waiting for the transaction to confirm
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
+        /// Don't check that the wallet holds enough CAT and XCH before launching
+        #[arg(long, default_value_t = false)]
+        skip_balance_check: bool,
+        /// Accept rows with identical parameters (they create identical coins, so Sage has to
+        /// fund them from different coins or the transaction is rejected)
+        #[arg(long, default_value_t = false)]
+        allow_duplicates: bool,
     },
 
     #[command(arg_required_else_help = true)]
     View {
+        #[arg(required_unless_present = "from_file")]
+        stream_id: Option<String>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+        /// Stream file written by `export`, instead of the stream id (and --testnet11)
+        #[arg(long, conflicts_with_all = ["stream_id", "testnet11"])]
+        from_file: Option<String>,
+        #[arg(long, default_value_t = false)]
+        raw: bool,
+        #[arg(long)]
+        max_spends: Option<usize>,
+        /// Only show claims made at or after this time (unix timestamp or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+        /// Average block time (in seconds) used for the per-block projection
+        #[arg(long, default_value_t = AVERAGE_BLOCK_TIME)]
+        block_time: u64,
+        /// Print a single summary line instead of the history
+        #[arg(long, default_value_t = false, conflicts_with_all = ["raw", "max_spends", "since"])]
+        compact: bool,
+        /// Print the stream's full timeline (parameters, claims and current state) as JSON
+        #[arg(long, default_value_t = false, conflicts_with_all = ["raw", "max_spends", "since", "compact"])]
+        json: bool,
+    },
+
+    #[command(arg_required_else_help = true)]
+    Discover {
+        recipient: String,
+        #[arg(long, default_value_t = false)]
+        include_finished: bool,
+        #[arg(long, default_value_t = 0)]
+        start_height: u32,
+        /// Ignore the progress saved by previous runs and scan from --start-height again
+        #[arg(long, default_value_t = false)]
+        restart: bool,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+    },
+
+    /// Prints the address a stream with the given parameters is sent to (no network access)
+    #[command(arg_required_else_help = true)]
+    StreamAddress {
+        #[arg(long)]
+        recipient: String,
+        /// Clawback address, or 'none'
+        #[arg(long)]
+        clawback_address: String,
+        #[arg(long)]
+        start: u64,
+        #[arg(long)]
+        end: u64,
+        #[arg(long)]
+        asset_id: Option<String>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+    },
+
+    /// Checks that a stream vests what you were promised
+    #[command(arg_required_else_help = true)]
+    Verify {
+        #[arg(required_unless_present = "from_file")]
+        stream_id: Option<String>,
+        #[arg(long, required_unless_present = "from_file")]
+        recipient: Option<String>,
+        #[arg(long, required_unless_present = "from_file")]
+        amount: Option<String>,
+        #[arg(long, required_unless_present = "from_file")]
+        start: Option<u64>,
+        #[arg(long, required_unless_present = "from_file")]
+        end: Option<u64>,
+        #[arg(long)]
+        clawback_address: Option<String>,
+        #[arg(long)]
+        asset_id: Option<String>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+        /// Stream file written by `export` - checks the stream against all of its parameters
+        #[arg(long, conflicts_with_all = ["stream_id", "recipient", "amount", "start", "end", "clawback_address", "asset_id", "testnet11"])]
+        from_file: Option<String>,
+    },
+
+    /// Writes a stream's parameters to a JSON file that can be shared with the recipient
+    #[command(arg_required_else_help = true)]
+    Export {
+        stream_id: String,
+        #[arg(long)]
+        out: String,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+    },
+
+    /// Serves a read-only HTTP API mirroring view, discover and simulate
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:3000")]
+        bind: String,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+    },
+
+    /// Prints the embedded stream puzzle and checks its hash (no network access)
+    PuzzleInfo,
+
+    #[command(arg_required_else_help = true)]
+    Simulate {
+        amount: String,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        claim_times: Vec<u64>,
+        #[arg(long)]
+        clawback_at: Option<u64>,
+        /// Asset id (or ticker from --cat-metadata) of the CAT, to display amounts with its
+        /// ticker and decimals
+        #[arg(long)]
+        asset_id: Option<String>,
+    },
+
+    /// Prints the coin id of the streaming coin a claim would create (no transaction is made)
+    #[command(arg_required_else_help = true)]
+    NextCoin {
+        stream_id: String,
+        /// Claim time (unix timestamp or RFC 3339) - defaults to the latest possible claim time
+        #[arg(long)]
+        at: Option<String>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+    },
+
+    /// Projects the claims (and total fees) of claiming a stream at a fixed interval
+    #[command(arg_required_else_help = true)]
+    EstimateSchedule {
         stream_id: String,
+        /// Time between claims, e.g. 3600, 30m, 12h or 7d
+        #[arg(long)]
+        interval: String,
+        #[arg(long, default_value = "0.0001")]
+        fee: String,
         #[arg(long, default_value_t = false)]
         testnet11: bool,
     },
 
     #[command(arg_required_else_help = true)]
     Claim {
+        stream_id: String,
+        #[arg(long)]
+        amount: Option<String>,
+        #[arg(long, default_value = "0.0001")]
+        fee: String,
+        /// Fee in mojos, instead of --fee
+        #[arg(long, conflicts_with = "fee")]
+        fee_mojos: Option<u64>,
+        #[arg(long, default_value_t = false)]
+        testnet11: bool,
+        #[arg(long, default_value_t = false)]
+        hardened: bool,
+        /// Search both unhardened and hardened derivations, if you're not sure which one is used
+        #[arg(long, default_value_t = false, conflicts_with = "hardened")]
+        auto: bool,
+        #[arg(long, default_value = "10000")]
+        max_derivations: u64,
+        /// Ignore the cached address -> public key mapping and re-query Sage
+        #[arg(long, default_value_t = false)]
+        refresh_derivations: bool,
+        /// Don't warn when the fee is below the current mempool minimum
+        #[arg(long, default_value_t = false)]
+        allow_low_fee: bool,
+        #[arg(long)]
+        state_token: Option<String>,
+        /// Signed spend bundle (JSON) sending the authorization message, for non-standard wallets
+        #[arg(long)]
+        message_spend: Option<String>,
+        /// File with a master secret key (hex) or mnemonic - signs locally instead of via Sage
+        #[arg(long, conflicts_with = "message_spend")]
+        signer_file: Option<String>,
+        /// Return right after submitting instead of waiting for the transaction to confirm
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
+        /// After confirmation, check that the recipient received the claimed CAT coin
+        #[arg(long, default_value_t = false, conflicts_with = "no_wait")]
+        verify_payout: bool,
+        /// Claim even if the remaining coin would be frozen until the stream ends
+        #[arg(long, default_value_t = false)]
+        allow_dust: bool,
+        /// Wait for the next transaction block and claim against its timestamp
+        #[arg(long, default_value_t = false, conflicts_with = "message_spend")]
+        wait_for_block: bool,
+        /// The recipient's public key (hex) - skips searching Sage's derivations for it
+        #[arg(long, conflicts_with_all = ["message_spend", "signer_file"])]
+        recipient_pubkey: Option<String>,
+    },
+
+    /// Claims whatever has vested so far, or exits successfully if there's nothing to claim
+    #[command(arg_required_else_help = true)]
+    SyncClaim {
         stream_id: String,
         #[arg(long, default_value = "0.0001")]
         fee: String,
+        /// Fee in mojos, instead of --fee
+        #[arg(long, conflicts_with = "fee")]
+        fee_mojos: Option<u64>,
         #[arg(long, default_value_t = false)]
         testnet11: bool,
         #[arg(long, default_value_t = false)]
         hardened: bool,
+        /// Search both unhardened and hardened derivations, if you're not sure which one is used
+        #[arg(long, default_value_t = false, conflicts_with = "hardened")]
+        auto: bool,
         #[arg(long, default_value = "10000")]
         max_derivations: u64,
+        /// Ignore the cached address -> public key mapping and re-query Sage
+        #[arg(long, default_value_t = false)]
+        refresh_derivations: bool,
+        /// Don't warn when the fee is below the current mempool minimum
+        #[arg(long, default_value_t = false)]
+        allow_low_fee: bool,
+        /// Return right after submitting instead of waiting for the transaction to confirm
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
+        /// After confirmation, check that the recipient received the claimed CAT coin
+        #[arg(long, default_value_t = false, conflicts_with = "no_wait")]
+        verify_payout: bool,
+        /// Only claim once at least this much has vested (same format as the launch amount)
+        #[arg(long)]
+        min_claim_value: Option<String>,
+        /// Only claim once this long (e.g., 12h or 7d) has passed since the last claim
+        #[arg(long)]
+        min_interval: Option<String>,
     },
 
     #[command(arg_required_else_help = true)]
@@ -70,39 +403,325 @@ enum Commands {
         stream_id: String,
         #[arg(long, default_value = "0.0001")]
         fee: String,
+        /// Fee in mojos, instead of --fee
+        #[arg(long, conflicts_with = "fee")]
+        fee_mojos: Option<u64>,
         #[arg(long, default_value_t = false)]
         testnet11: bool,
         #[arg(long, default_value_t = false)]
         hardened: bool,
+        /// Search both unhardened and hardened derivations, if you're not sure which one is used
+        #[arg(long, default_value_t = false, conflicts_with = "hardened")]
+        auto: bool,
         #[arg(long, default_value = "10000")]
         max_derivations: u64,
+        /// Ignore the cached address -> public key mapping and re-query Sage
+        #[arg(long, default_value_t = false)]
+        refresh_derivations: bool,
+        /// Don't warn when the fee is below the current mempool minimum
+        #[arg(long, default_value_t = false)]
+        allow_low_fee: bool,
+        #[arg(long)]
+        state_token: Option<String>,
+        /// Signed spend bundle (JSON) sending the authorization message, for non-standard wallets
+        #[arg(long)]
+        message_spend: Option<String>,
+        /// File with a master secret key (hex) or mnemonic - signs locally instead of via Sage
+        #[arg(long, conflicts_with = "message_spend")]
+        signer_file: Option<String>,
+        /// Payment time used to split the stream - must be after the latest block timestamp
+        #[arg(long, conflicts_with_all = ["fair", "message_spend"])]
+        payment_time: Option<u64>,
+        /// Pay the recipient exactly what has vested as of the latest block
+        #[arg(long, default_value_t = false, conflicts_with = "message_spend")]
+        fair: bool,
+        /// Return right after submitting instead of waiting for the transaction to confirm
+        #[arg(long, default_value_t = false)]
+        no_wait: bool,
     },
 }
 
+#[derive(Debug, Serialize)]
+struct LaunchResult {
+    stream_id: String,
+    streaming_coin_id: String,
+    streaming_cat_address: String,
+    inner_puzzle_hash: String,
+    memos: Vec<String>,
+}
+
 #[derive(Error, Debug)]
 enum CliError {
     #[error("Invalid asset id")]
     InvalidAssetId,
+    #[error("Unknown CAT '{0}' - expected a 64-character asset id or a ticker from the --cat-metadata file")]
+    UnknownCat(String),
+    #[error(
+        "Ticker '{0}' matches several CATs in the --cat-metadata file - use the asset id instead"
+    )]
+    AmbiguousTicker(String),
     #[error("Home directory not found")]
     HomeDirectoryNotFound,
     #[error("Sage client error")]
     SageClient(#[from] client::ClientError),
-    #[error("Invalid amount: The amount is in XCH/CAT units, not mojos. Please include a '.' in the amount to indicate that you understand.")]
+    #[error("Invalid amount: The amount is in XCH/CAT units, not mojos. Please include a '.' in the amount to indicate that you understand, or use an 'm' suffix for mojos (e.g., 5000m).")]
     InvalidAmount,
     #[error("Invalid address")]
     Address(#[from] AddressError),
-    #[error("Invalid stream id")]
-    InvalidStreamId(),
-    #[error("Failed to encode address")]
-    EncodeAddress(#[from] bech32::Error),
     #[error("Failed to get streaming coin id - streaming CAT might exist, but the CLI was unable to find it.")]
     UnknownStreamingCoinId,
     #[error("Coinset.org request failed")]
     Reqwest(#[from] reqwest::Error),
+    #[error("Failed to get blockchain state from coinset.org")]
+    BlockchainStateUnavailable,
+    #[error("Failed to get block record from coinset.org")]
+    BlockRecordUnavailable,
+    /// Derivations searched, and whether both keyspaces were (`--auto`)
+    #[error("No key for the address within the first {0} derivations - {}", derivation_hint(*.1))]
+    PublicKeyNotFound(u64, bool),
+    #[error("Sage has no active key - log in to a wallet first")]
+    NoActiveKey,
+    #[error("Invalid public key - expected 48 hex-encoded bytes")]
+    InvalidPublicKey,
+    #[error("The public key does not match the stream's recipient")]
+    RecipientKeyMismatch,
+    #[error("--max-derivations must be at least 1")]
+    InvalidMaxDerivations,
+    #[error("Wallet is using a non-standard puzzle for this address - use --message-spend to authorize the spend externally")]
+    NonStandardPuzzle,
+    #[error("Failed to find the lead coin in the Sage transaction")]
+    LeadCoinNotFound,
+    #[error("Stream cannot be clawed back - it has no clawback address")]
+    NoClawbackAddress,
+    #[error("Coinset.org request was unsuccessful: {0}")]
+    CoinsetUnsuccessful(String),
+    #[error("Stream has no spendable coin - it might have been fully claimed or clawed back")]
+    StreamUnavailable,
+    #[error("Coin {0} not found - check the stream id and network")]
+    CoinRecordNotFound(String),
+    #[error("Coinset.org keeps returning an incomplete record for coin {0}")]
+    CoinRecordIncomplete(String),
+    #[error("Could not parse the stream at coin {0}")]
+    StreamParseFailed(String),
     #[error("Driver error")]
     Driver(#[from] DriverError),
+    #[error(transparent)]
+    Streaming(#[from] StreamingError),
     #[error("Hex decoding failed")]
     HexDecodingFailed(#[from] hex::FromHexError),
+    #[error("JSON serialization failed")]
+    Json(#[from] serde_json::Error),
+    #[error(
+        "Invalid claim schedule: payment times must be increasing and within the stream window"
+    )]
+    InvalidClaimSchedule,
+    #[error("Requested amount exceeds the remaining streamed amount")]
+    ClaimAmountTooLarge,
+    #[error("Requested amount has not vested yet - it can be claimed after {0}")]
+    ClaimAmountNotVested(u64),
+    #[error("Claim time {0} is not before the latest block timestamp {1} - the spend would never confirm")]
+    ClaimTimeInFuture(u64, u64),
+    #[error("Stream has fully vested - there is nothing left to claw back")]
+    StreamFullyVested,
+    #[error("Stream does not match the expected parameters ({0} mismatches)")]
+    StreamMismatch(usize),
+    #[error("Failed to read file: {0}")]
+    ReadFile(std::io::Error),
+    #[error("Failed to write file: {0}")]
+    WriteFile(std::io::Error),
+    #[error("Invalid stream file: {0}")]
+    InvalidExportFile(String),
+    #[error("Invalid message spend: {0}")]
+    InvalidMessageSpend(String),
+    #[error("Invalid CAT metadata file - expected a JSON object mapping asset ids to {{\"ticker\", \"decimals\"}}")]
+    InvalidCatMetadata,
+    #[error("Address has prefix '{0}', expected '{1}' - check the address and --testnet11")]
+    WrongAddressPrefix(String, String),
+    #[error("Invalid spend bundle: {0}")]
+    InvalidSpendBundle(String),
+    #[error("Claim would leave dust that can't be claimed until the stream ends - claim a different amount or use --allow-dust")]
+    DustRemaining,
+    #[error("Invalid signer file - expected a hex-encoded master secret key or a mnemonic")]
+    InvalidSignerFile,
+    #[error("No unspent XCH coin at the address to send the authorization and pay the fee from")]
+    NoFeeCoin,
+    #[error("Coinset.org has no puzzle and solution for spent coin {0}")]
+    PuzzleAndSolutionUnavailable(Bytes32),
+    #[error("Invalid timestamp '{0}' - expected a unix timestamp or an RFC 3339 date")]
+    InvalidTimestamp(String),
+    #[error("Payout coin {0} was not found - the claim may not have paid the recipient")]
+    PayoutNotFound(Bytes32),
+    #[error("Invalid duration '{0}' - expected a number of seconds, optionally with an s/m/h/d/w suffix")]
+    InvalidDuration(String),
+    #[error("Invalid genesis challenge - expected 32 hex-encoded bytes")]
+    InvalidGenesisChallenge,
+    #[error("The embedded stream puzzle does not match its hash")]
+    PuzzleHashMismatch,
+    #[error("Sage is on a different network (address prefix '{0}', expected '{1}') - switch networks in Sage or check --testnet11")]
+    SageNetworkMismatch(String, String),
+    #[error("Invalid batch file: {0}")]
+    InvalidBatchFile(String),
+    #[error("Batch total {0} does not match the sum of the stream amounts {1}")]
+    BatchTotalMismatch(u64, u64),
+    #[error("Insufficient {0} balance in Sage: {1} available, {2} needed")]
+    InsufficientBalance(String, String, String),
+    #[error("Claim time {0} is outside the stream's claim window ({1} to {2})")]
+    ClaimTimeOutsideWindow(u64, u64, u64),
+    #[error("Payment time {0} is outside the stream's remaining window ({1} to {2})")]
+    PaymentTimeOutsideWindow(u64, u64, u64),
+    #[error("Launched coin does not match the stream: {0}")]
+    LaunchMismatch(String),
+    #[error("Discovery can scan at most {0} blocks - pass a later start height")]
+    DiscoverRangeTooLarge(u32),
+    #[error("State token does not belong to stream {0}")]
+    StateTokenMismatch(String),
+    #[error("Payment time {0} is not after the latest block timestamp {1} - a clawback with it can never confirm")]
+    PaymentTimePassed(u64, u64),
+    #[error("Aborted: no confirmation within {0}")]
+    Aborted(String),
+    #[cfg(feature = "server")]
+    #[error("Server error: {0}")]
+    Server(std::io::Error),
+}
+
+/// Failure category of a `CliError`. Both the exit code and the HTTP status of the server are
+/// derived from it, so they can't disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    Internal,
+    InvalidInput,
+    /// Network / coinset.org error
+    Network,
+    /// Stream, coin or key not found, or nothing claimable
+    NotFound,
+    Sage,
+    /// No answer to a confirmation prompt within --confirm-timeout
+    Aborted,
+}
+
+impl ErrorKind {
+    // Exit codes are documented in the README - keep them stable
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Internal => 1,
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::Network => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Sage => 5,
+            ErrorKind::Aborted => 6,
+        }
+    }
+}
+
+// With --auto both keyspaces were already searched, so only a higher limit can help
+fn derivation_hint(auto: bool) -> &'static str {
+    if auto {
+        "both unhardened and hardened derivations were searched, try increasing --max-derivations"
+    } else {
+        "try increasing --max-derivations, or toggling --hardened (or --auto to search both)"
+    }
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        self.kind().exit_code()
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CliError::Driver(_)
+            | CliError::Json(_)
+            | CliError::ClaimTimeInFuture(_, _)
+            | CliError::InvalidSpendBundle(_)
+            | CliError::PuzzleHashMismatch
+            | CliError::LaunchMismatch(_)
+            | CliError::WriteFile(_)
+            | CliError::Streaming(StreamingError::Bech32(_) | StreamingError::Driver(_)) => {
+                ErrorKind::Internal
+            }
+            CliError::InvalidAssetId
+            | CliError::UnknownCat(_)
+            | CliError::AmbiguousTicker(_)
+            | CliError::InvalidAmount
+            | CliError::Address(_)
+            | CliError::HexDecodingFailed(_)
+            | CliError::InvalidClaimSchedule
+            | CliError::ClaimAmountTooLarge
+            | CliError::ReadFile(_)
+            | CliError::InvalidExportFile(_)
+            | CliError::InvalidMessageSpend(_)
+            | CliError::InvalidCatMetadata
+            | CliError::WrongAddressPrefix(_, _)
+            | CliError::PaymentTimePassed(_, _)
+            | CliError::StateTokenMismatch(_)
+            | CliError::DiscoverRangeTooLarge(_)
+            | CliError::InvalidBatchFile(_)
+            | CliError::InvalidTimestamp(_)
+            | CliError::ClaimTimeOutsideWindow(_, _, _)
+            | CliError::PaymentTimeOutsideWindow(_, _, _)
+            | CliError::InvalidDuration(_)
+            | CliError::InvalidGenesisChallenge
+            | CliError::DustRemaining
+            | CliError::InvalidSignerFile
+            | CliError::InvalidMaxDerivations
+            | CliError::InvalidPublicKey
+            | CliError::RecipientKeyMismatch
+            | CliError::BatchTotalMismatch(_, _)
+            | CliError::Streaming(_) => ErrorKind::InvalidInput,
+            CliError::Reqwest(_)
+            | CliError::CoinsetUnsuccessful(_)
+            | CliError::BlockchainStateUnavailable
+            | CliError::BlockRecordUnavailable
+            | CliError::CoinRecordIncomplete(_)
+            | CliError::PuzzleAndSolutionUnavailable(_) => ErrorKind::Network,
+            CliError::UnknownStreamingCoinId
+            | CliError::StreamUnavailable
+            | CliError::CoinRecordNotFound(_)
+            | CliError::StreamParseFailed(_)
+            | CliError::PublicKeyNotFound(_, _)
+            | CliError::NoClawbackAddress
+            | CliError::ClaimAmountNotVested(_)
+            | CliError::StreamFullyVested
+            | CliError::StreamMismatch(_)
+            | CliError::NoFeeCoin
+            | CliError::PayoutNotFound(_) => ErrorKind::NotFound,
+            #[cfg(feature = "server")]
+            CliError::Server(_) => ErrorKind::Internal,
+            CliError::HomeDirectoryNotFound
+            | CliError::SageClient(_)
+            | CliError::NonStandardPuzzle
+            | CliError::LeadCoinNotFound
+            | CliError::SageNetworkMismatch(_, _)
+            | CliError::NoActiveKey
+            | CliError::InsufficientBalance(_, _, _) => ErrorKind::Sage,
+            CliError::Aborted(_) => ErrorKind::Aborted,
+        }
+    }
+}
+
+/// `--testnet11` still selects the address/stream prefixes when a custom URL is given.
+fn coinset_client(testnet11: bool, coinset_url: Option<&str>) -> CoinsetClient {
+    match coinset_url {
+        Some(coinset_url) => CoinsetClient::new(coinset_url.to_string()),
+        None if testnet11 => CoinsetClient::testnet11(),
+        None => CoinsetClient::mainnet(),
+    }
+}
+
+/// Decodes a wallet address for the selected network. `Address::decode` already rejects
+/// payloads that aren't 32 bytes; the prefix check catches other bech32m strings (e.g., a
+/// stream id or an address for the other network) that would otherwise lock funds away.
+fn decode_address(address: &str, testnet11: bool) -> Result<Bytes32, CliError> {
+    let address = Address::decode(address)?;
+    let expected_prefix = get_address_prefix(testnet11);
+    if address.prefix != expected_prefix {
+        return Err(CliError::WrongAddressPrefix(
+            address.prefix,
+            expected_prefix,
+        ));
+    }
+
+    Ok(address.puzzle_hash)
 }
 
 fn get_address_prefix(testnet11: bool) -> String {
@@ -121,7 +740,28 @@ fn get_stream_prefix(testnet11: bool) -> String {
     }
 }
 
+fn get_state_token_prefix(testnet11: bool) -> String {
+    if testnet11 {
+        "tstreamstate".to_string()
+    } else {
+        "streamstate".to_string()
+    }
+}
+
+/// Fee in mojos: `--fee-mojos` if given, otherwise `--fee` (in XCH).
+fn parse_fee(fee: String, fee_mojos: Option<u64>) -> Result<u64, CliError> {
+    match fee_mojos {
+        Some(fee_mojos) => Ok(fee_mojos),
+        None => parse_amount(fee, false),
+    }
+}
+
 fn parse_amount(amount: String, is_cat: bool) -> Result<u64, CliError> {
+    // Integers with an 'm' suffix (e.g., "5000m") are taken as mojos as-is
+    if let Some(mojos) = amount.strip_suffix('m') {
+        return mojos.parse::<u64>().map_err(|_| CliError::InvalidAmount);
+    }
+
     if !amount.contains(".") {
         return Err(CliError::InvalidAmount);
     }
@@ -148,46 +788,352 @@ fn parse_amount(amount: String, is_cat: bool) -> Result<u64, CliError> {
     }
 }
 
+/// Finds the coin id of the `amount` streaming CAT sent to `streaming_cat_address` in a Sage
+/// transaction summary.
+fn find_streaming_coin_id(
+    response: &SendCatResponse,
+    asset_id: [u8; 32],
+    streaming_cat_address: &str,
+    amount: u64,
+) -> Result<Bytes32, CliError> {
+    for coin in &response.summary.inputs {
+        let AssetKind::Cat {
+            asset_id: input_asset_id,
+            ..
+        } = &coin.kind
+        else {
+            continue;
+        };
+        if input_asset_id.replace("0x", "") != hex::encode(asset_id) {
+            continue;
+        }
+
+        for output in &coin.outputs {
+            if output.receiving
+                || output.address != streaming_cat_address
+                || amount_to_u64(&output.amount)? != amount
+            {
+                continue;
+            }
+
+            return hex::decode(output.coin_id.replace("0x", ""))
+                .map_err(|_| CliError::UnknownStreamingCoinId)?
+                .try_into()
+                .map_err(|_| CliError::UnknownStreamingCoinId);
+        }
+    }
+
+    Err(CliError::UnknownStreamingCoinId)
+}
+
+/// Parses a duration in seconds, optionally with an s/m/h/d/w suffix (e.g., 90m or 12h).
+fn parse_duration(duration: &str) -> Result<u64, CliError> {
+    let (number, unit) = match duration.char_indices().last() {
+        Some((index, 's')) => (&duration[..index], 1),
+        Some((index, 'm')) => (&duration[..index], 60),
+        Some((index, 'h')) => (&duration[..index], 60 * 60),
+        Some((index, 'd')) => (&duration[..index], 24 * 60 * 60),
+        Some((index, 'w')) => (&duration[..index], 7 * 24 * 60 * 60),
+        _ => (duration, 1),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .filter(|seconds| *seconds > 0)
+        .ok_or_else(|| CliError::InvalidDuration(duration.to_string()))
+}
+
+/// Formats a duration as its largest unit and the next one, e.g. "3 days, 4 hours".
+fn format_duration(seconds: u64) -> String {
+    const UNITS: [(&str, u64); 5] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+    let plural =
+        |count: u64, unit: &str| format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+
+    let Some(index) = UNITS.iter().position(|(_, size)| seconds >= *size) else {
+        return plural(0, "second");
+    };
+    let (unit, size) = UNITS[index];
+    let mut formatted = plural(seconds / size, unit);
+    if let Some((next_unit, next_size)) = UNITS.get(index + 1) {
+        let count = seconds % size / next_size;
+        if count > 0 {
+            formatted.push_str(&format!(", {}", plural(count, next_unit)));
+        }
+    }
+
+    formatted
+}
+
+/// Parses a unix timestamp or an RFC 3339 date (e.g., 2025-01-31T12:00:00Z).
+fn parse_timestamp(timestamp: &str) -> Result<u64, CliError> {
+    if let Ok(timestamp) = timestamp.parse::<u64>() {
+        return Ok(timestamp);
+    }
+
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .and_then(|date| u64::try_from(date.timestamp()).ok())
+        .ok_or_else(|| CliError::InvalidTimestamp(timestamp.to_string()))
+}
+
+/// Prints the launch memos with their meaning, so the encoded parameters can be checked
+/// before any funds move. The memos are decoded back into stream parameters, which makes sure
+/// they can be parsed the same way once the stream is on-chain.
+fn print_launch_memos(memos: &[Bytes], prefix: &str) -> Result<(), CliError> {
+    let params = StreamParameters::from_hints(memos)?;
+
+    let mut meanings = vec![format!(
+        "hint: 0x{}",
+        hex::encode(params.recipient.to_vec())
+    )];
+    if let Some(clawback_ph) = params.clawback_ph {
+        meanings.push(format!(
+            "clawback address: {}",
+            Address::new(clawback_ph, prefix.to_string()).encode()?
+        ));
+    }
+    for (name, timestamp) in [
+        ("start time", params.start_time),
+        ("end time", params.end_time),
+    ] {
+        meanings.push(format!(
+            "{}: {} (local: {})",
+            name,
+            timestamp,
+            Local
+                .timestamp_opt(timestamp as i64, 0)
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+
+    eprintln!("Launch memos:");
+    for (i, meaning) in meanings.iter().enumerate() {
+        eprintln!("  {}. {}", i, meaning);
+    }
+
+    Ok(())
+}
+
+/// Payment times have to be increasing and within the stream window.
+fn check_claim_schedule(
+    start_timestamp: u64,
+    end_timestamp: u64,
+    claim_times: &[u64],
+    clawback_at: Option<u64>,
+) -> Result<(), CliError> {
+    let payment_times: Vec<u64> = claim_times.iter().copied().chain(clawback_at).collect();
+    if payment_times.windows(2).any(|w| w[0] > w[1])
+        || payment_times
+            .iter()
+            .any(|time| *time < start_timestamp || *time > end_timestamp)
+    {
+        return Err(CliError::InvalidClaimSchedule);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SyncOptions<'a> {
+    print: bool,
+    print_claimable: bool,
+    raw: bool,
+    /// Stop after this many spends (counted forward from the launch coin). The returned
+    /// stream is then the coin reached at that point, not the latest one.
+    max_spends: Option<usize>,
+    /// Used to display amounts - defaults to "CAT" with 3 decimals
+    cat_metadata: Option<&'a CatMetadataMap>,
+    /// Only print claims made at or after this timestamp. The whole chain is still walked.
+    since: Option<u64>,
+    /// Block time for the per-block projection - defaults to `AVERAGE_BLOCK_TIME`
+    block_time: Option<u64>,
+    /// Fetch the block timestamp of every claim so it ends up in the history (they're always
+    /// fetched when printing)
+    claim_timestamps: bool,
+}
+
+/// What `sync_stream_history` saw on the way to the latest coin.
+#[derive(Debug, Default)]
+struct SyncHistory {
+    /// The stream as launched, before any claims
+    launch: Option<StreamedCat>,
+    /// Only recorded if the claim timestamps were fetched (`print` or `claim_timestamps`)
+    claims: Vec<StreamClaim>,
+}
+
+const PUZZLE_AND_SOLUTION_ATTEMPTS: u32 = 4;
+
+/// Coinset can report a coin as spent before it has indexed the spend itself, so a missing
+/// puzzle and solution is retried with backoff before the spend is considered unavailable.
+async fn retry_puzzle_and_solution(
+    cli: &CoinsetClient,
+    coin_id: Bytes32,
+    spent_block_index: u32,
+) -> Result<CoinSpend, CliError> {
+    let mut backoff = tokio::time::Duration::from_secs(1);
+    for _ in 1..PUZZLE_AND_SOLUTION_ATTEMPTS {
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+
+        let resp = cli
+            .get_puzzle_and_solution(coin_id, Some(spent_block_index))
+            .await
+            .map_err(CliError::Reqwest)?;
+        if !resp.success {
+            return Err(CliError::CoinsetUnsuccessful(
+                resp.error.unwrap_or_default(),
+            ));
+        }
+        if let Some(coin_solution) = resp.coin_solution {
+            return Ok(coin_solution);
+        }
+    }
+
+    Err(CliError::PuzzleAndSolutionUnavailable(coin_id))
+}
+
+const COIN_RECORD_ATTEMPTS: u32 = 4;
+
+/// Coinset can return a successful response whose coin is zeroed out or incomplete (e.g.,
+/// while indexing or during a reorg). Such a coin doesn't hash to the requested id, and
+/// following its parent id would walk off into nonexistent coins.
+fn is_degenerate_coin_record(coin_record: &CoinRecord, coin_id: Bytes32) -> bool {
+    coin_record.coin.puzzle_hash == Bytes32::default() || coin_record.coin.coin_id() != coin_id
+}
+
+/// Fetches the record of `coin_id`, retrying degenerate records with backoff. Returns `None`
+/// if the coin isn't known, and an error if coinset keeps returning a degenerate record for it.
+async fn get_coin_record(
+    cli: &CoinsetClient,
+    coin_id: Bytes32,
+) -> Result<Option<CoinRecord>, CliError> {
+    let mut backoff = tokio::time::Duration::from_secs(1);
+    for attempt in 1..=COIN_RECORD_ATTEMPTS {
+        let resp = cli
+            .get_coin_record_by_name(coin_id)
+            .await
+            .map_err(CliError::Reqwest)?;
+        if !resp.success {
+            return Err(CliError::CoinsetUnsuccessful(
+                resp.error.unwrap_or_default(),
+            ));
+        }
+
+        match resp.coin_record {
+            Some(coin_record) if is_degenerate_coin_record(&coin_record, coin_id) => {
+                if attempt < COIN_RECORD_ATTEMPTS {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+            coin_record => return Ok(coin_record),
+        }
+    }
+
+    Err(CliError::CoinRecordIncomplete(hex::encode(
+        coin_id.to_vec(),
+    )))
+}
+
+// A clawback can only return funds to the clawback address, so clawing back to the recipient
+// itself achieves nothing - this is almost always a copy-paste mistake
+const CLAWBACK_IS_RECIPIENT_WARNING: &str =
+    "Warning: the clawback address is the recipient address, so a clawback would only return the CAT to the recipient.";
+
+/// Coin whose spend the walk parses first (the launch coin's parent, which created it) and
+/// whether the launch coin itself is still unspent, i.e., is also the latest streaming coin.
+fn launch_walk_start(launch_coin_record: &CoinRecord) -> (Bytes32, bool) {
+    (
+        launch_coin_record.coin.parent_coin_info,
+        launch_coin_record.spent_block_index == 0,
+    )
+}
+
+/// Walks a stream from its launch coin to the latest unspent streaming coin. Returns `None`
+/// only if the stream has ended in a final claim or a clawback; a coin that can't be found or
+/// parsed is an error.
 async fn sync_stream(
     stream_id: String,
     cli: &CoinsetClient,
+    timestamps: &mut TimestampCache,
     stream_prefix: String,
     prefix: String,
-    print: bool,
-    print_claimable: bool,
+    options: SyncOptions<'_>,
 ) -> Result<Option<StreamedCat>, CliError> {
-    println!("Viewing stream with id {stream_id}");
+    Ok(
+        sync_stream_history(stream_id, cli, timestamps, stream_prefix, prefix, options)
+            .await?
+            .0,
+    )
+}
+
+/// Same as `sync_stream`, but also returns the launch coin and the claims made so far.
+///
+/// A single `SpendContext` is reused to parse every parent spend in the traversal, so the
+/// allocator grows once instead of being recreated per coin. The context is owned by this
+/// call and never shared across tasks/threads - concurrent syncs each get their own.
+///
+/// The sync can be cancelled by dropping the future (e.g., with `tokio::select!` or a timeout):
+/// it only awaits coinset.org requests, which are aborted when dropped, and all of its state
+/// is local apart from `timestamps`, which only ever holds complete entries.
+async fn sync_stream_history(
+    stream_id: String,
+    cli: &CoinsetClient,
+    timestamps: &mut TimestampCache,
+    stream_prefix: String,
+    prefix: String,
+    options: SyncOptions<'_>,
+) -> Result<(Option<StreamedCat>, SyncHistory), CliError> {
+    let SyncOptions {
+        print,
+        print_claimable,
+        raw,
+        max_spends,
+        cat_metadata,
+        since,
+        block_time,
+        claim_timestamps,
+    } = options;
 
-    let stream_coin_id = Address::decode(&stream_id).map_err(|_| CliError::InvalidStreamId())?;
-    if stream_coin_id.prefix != stream_prefix {
-        return Err(CliError::InvalidStreamId());
+    if print {
+        eprintln!("Viewing stream with id {stream_id}");
     }
-    let stream_coin_id = Bytes32::from(stream_coin_id.puzzle_hash);
+
+    let StreamId(stream_coin_id) = StreamId::decode(&stream_id, &stream_prefix)?;
 
     let mut first_run = true;
+    let mut launch_coin_unspent = false;
     let mut ctx = SpendContext::new();
     let mut latest_coin_id = stream_coin_id;
     let mut latest_stream = None;
+    let mut spends = 0;
+    let mut truncated = false;
+    let mut metadata = CatMetadata::default();
+    let mut hidden_spends = 0;
+    let mut history = SyncHistory::default();
 
     loop {
-        let coin_record_resp = cli
-            .get_coin_record_by_name(latest_coin_id)
-            .await
-            .map_err(CliError::Reqwest)?;
-
-        if !coin_record_resp.success {
-            println!("Failed to get coin record :(");
-            return Ok(None);
-        }
-
-        let Some(coin_record) = coin_record_resp.coin_record else {
-            println!("Coin record not available");
-            return Ok(None);
+        let Some(coin_record) = get_coin_record(cli, latest_coin_id).await? else {
+            return Err(CliError::CoinRecordNotFound(hex::encode(
+                latest_coin_id.to_vec(),
+            )));
         };
+        if raw {
+            print_raw_coin_record(&coin_record);
+        }
 
         if first_run {
             // Parse parent spend to get first stream
-            latest_coin_id = coin_record.coin.parent_coin_info;
+            (latest_coin_id, launch_coin_unspent) = launch_walk_start(&coin_record);
             first_run = false;
             continue;
         }
@@ -202,48 +1148,160 @@ async fn sync_stream(
             break;
         }
 
-        let puzzle_and_solution = cli
-            .get_puzzle_and_solution(
+        // Each coin id is only known once its parent spend is parsed, so the chain itself has
+        // to be walked sequentially - but the spend's block timestamp (only needed for printing)
+        // can be fetched alongside its puzzle and solution
+        let is_launch_coin = latest_stream.is_none();
+        let (puzzle_and_solution, spent_timestamp) = tokio::join!(
+            cli.get_puzzle_and_solution(
                 coin_record.coin.coin_id(),
                 Some(coin_record.spent_block_index),
-            )
-            .await
-            .map_err(CliError::Reqwest)?;
-        let Some(coin_solution) = puzzle_and_solution.coin_solution else {
-            println!("Failed to get puzzle and solution");
-            return Ok(None);
+            ),
+            async {
+                if (print || claim_timestamps) && !is_launch_coin {
+                    timestamps
+                        .get_block_timestamp(cli, coin_record.spent_block_index)
+                        .await
+                        .map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+        );
+        let puzzle_and_solution = puzzle_and_solution.map_err(CliError::Reqwest)?;
+        let spent_timestamp = spent_timestamp?;
+        if !puzzle_and_solution.success {
+            return Err(CliError::CoinsetUnsuccessful(
+                puzzle_and_solution.error.unwrap_or_default(),
+            ));
+        }
+        let coin_solution = match puzzle_and_solution.coin_solution {
+            Some(coin_solution) => coin_solution,
+            None => {
+                retry_puzzle_and_solution(
+                    cli,
+                    coin_record.coin.coin_id(),
+                    coin_record.spent_block_index,
+                )
+                .await?
+            }
         };
+        if raw {
+            println!(
+                "  [raw] puzzle reveal: 0x{}",
+                hex::encode(coin_solution.puzzle_reveal.to_vec())
+            );
+            println!(
+                "  [raw] solution: 0x{}",
+                hex::encode(coin_solution.solution.to_vec())
+            );
+        }
 
         let parent_puzzle = ctx.alloc(&coin_solution.puzzle_reveal)?;
         let parent_solution = ctx.alloc(&coin_solution.solution)?;
         let parent_puzzle = Puzzle::parse(&ctx, parent_puzzle);
 
-        let (new_stream, clawbacked, paid_amount_if_clawback) = StreamedCat::from_parent_spend(
+        let (mut new_stream, clawbacked, paid_amount_if_clawback) = StreamedCat::from_parent_spend(
             &mut ctx,
             coin_record.coin,
             parent_puzzle,
             parent_solution,
         )?;
+        let mut reject_reasons = Vec::new();
+        if new_stream.is_none() && is_launch_coin {
+            // Launches with extra memos (e.g., a note) are only picked up by the tolerant parser,
+            // which also explains why each output was rejected if none of them is a stream
+            (new_stream, reject_reasons) = parse_launch_diagnostic(
+                &mut ctx,
+                coin_record.coin,
+                parent_puzzle,
+                parent_solution,
+            )?;
+        }
+        if raw && !is_launch_coin {
+            let stream_solution = extract_solution(&mut ctx, &coin_solution)?;
+            println!(
+                "  [raw] stream solution: payment_time={} to_pay={} clawback={}",
+                stream_solution.payment_time, stream_solution.to_pay, stream_solution.clawback
+            );
+        }
+        // An empty child isn't a streaming coin - the spend was the final claim
+        let new_stream = new_stream.filter(|new_stream| new_stream.coin.amount > 0);
         let Some(new_stream) = new_stream else {
             if clawbacked {
+                if let Some(spent_timestamp) = spent_timestamp {
+                    history.claims.push(StreamClaim {
+                        coin_id: latest_coin_id,
+                        spent_block_index: coin_record.spent_block_index,
+                        timestamp: spent_timestamp,
+                        amount: paid_amount_if_clawback,
+                        clawback: true,
+                    });
+                }
                 if print {
                     println!(
-                        "  Streamed CAT was clawed back; last payment was {:.3} CATs.",
-                        paid_amount_if_clawback as f64 / 1000.0
+                        "  Streamed CAT was clawed back; last payment was {}.",
+                        metadata.format(paid_amount_if_clawback)
                     );
                 }
-            } else {
-                println!("Failed to parse streamed CAT");
-            }
-            return Ok(None);
-        };
+            } else if !is_launch_coin
+                && extract_solution(&mut ctx, &coin_solution)
+                    .is_ok_and(|solution| solution.to_pay == coin_record.coin.amount)
+            {
+                // The final claim pays out everything and doesn't recreate the streaming coin
+                if let Some(spent_timestamp) = spent_timestamp {
+                    history.claims.push(StreamClaim {
+                        coin_id: latest_coin_id,
+                        spent_block_index: coin_record.spent_block_index,
+                        timestamp: spent_timestamp,
+                        amount: coin_record.coin.amount,
+                        clawback: false,
+                    });
+                }
+                if print {
+                    println!(
+                        "  Coin {} spent at block {} to claim the remaining {}.",
+                        hex::encode(latest_coin_id.to_vec()),
+                        coin_record.spent_block_index,
+                        metadata.format(coin_record.coin.amount)
+                    );
+                }
+            } else {
+                for (index, reason) in &reject_reasons {
+                    eprintln!("  Output {}: {}", index, reason);
+                }
+                let coin_id = if is_launch_coin {
+                    stream_coin_id
+                } else {
+                    coin_record.coin.coin_id()
+                };
+                return Err(CliError::StreamParseFailed(hex::encode(coin_id.to_vec())));
+            }
+            // Only a final claim or a clawback ends the stream
+            return Ok((None, history));
+        };
+
+        if is_launch_coin {
+            history.launch = Some(new_stream);
+        } else if let Some(spent_timestamp) = spent_timestamp {
+            history.claims.push(StreamClaim {
+                coin_id: latest_coin_id,
+                spent_block_index: coin_record.spent_block_index,
+                timestamp: spent_timestamp,
+                amount: coin_record.coin.amount - new_stream.coin.amount,
+                clawback: false,
+            });
+        }
 
-        if latest_stream.is_none() && print {
+        if is_launch_coin {
+            metadata = cat_metadata
+                .map(|cat_metadata| cat_metadata.get(new_stream.asset_id))
+                .unwrap_or_default();
+        }
+
+        if is_launch_coin && print {
             println!("Asset id: {}", hex::encode(new_stream.asset_id.to_vec()));
-            println!(
-                "Total amount: {:.3}",
-                new_stream.coin.amount as f64 / 1000.0
-            );
+            println!("Total amount: {}", metadata.format(new_stream.coin.amount));
             println!(
                 "Recipient address: {}",
                 Address::new(new_stream.info.recipient, prefix.clone()).encode()?
@@ -256,6 +1314,9 @@ async fn sync_stream(
                     "None".to_string()
                 }
             );
+            if new_stream.info.clawback_ph == Some(new_stream.info.recipient) {
+                eprintln!("{}", CLAWBACK_IS_RECIPIENT_WARNING);
+            }
             println!(
                 "Start time: {} (local: {})",
                 new_stream.info.last_payment_time,
@@ -273,24 +1334,67 @@ async fn sync_stream(
                     .format("%Y-%m-%d %H:%M:%S")
             );
             println!("Spends:");
-        } else if print {
-            println!(
-                "  Coin {} spent at block {} to claim {} CATs.",
-                hex::encode(latest_coin_id.to_vec()),
-                coin_record.spent_block_index,
-                (coin_record.coin.amount - new_stream.coin.amount) as f64 / 1000.0
-            );
+        } else if let Some(spent_timestamp) = spent_timestamp.filter(|_| print) {
+            if since.is_some_and(|since| spent_timestamp < since) {
+                hidden_spends += 1;
+            } else {
+                if hidden_spends > 0 {
+                    println!("  ({} earlier spends not shown)", hidden_spends);
+                    hidden_spends = 0;
+                }
+                println!(
+                    "  Coin {} spent at block {} ({}) to claim {}.",
+                    hex::encode(latest_coin_id.to_vec()),
+                    coin_record.spent_block_index,
+                    Local
+                        .timestamp_opt(spent_timestamp as i64, 0)
+                        .unwrap()
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    metadata.format(coin_record.coin.amount - new_stream.coin.amount)
+                );
+            }
         }
 
         latest_coin_id = new_stream.coin.coin_id();
         latest_stream = Some(new_stream);
+
+        if is_launch_coin && launch_coin_unspent {
+            // Optimization: the launch coin's record was already fetched (and was unspent) on
+            // the first run, so it's the latest coin - the next iteration would only refetch
+            // that record to find the same thing
+            if print {
+                println!(
+                    "  Coin {} currently unspent.",
+                    hex::encode(latest_coin_id.to_vec())
+                );
+            }
+            break;
+        }
+
+        if !is_launch_coin {
+            spends += 1;
+        }
+        if max_spends.is_some_and(|max_spends| spends >= max_spends) {
+            truncated = true;
+            if print {
+                println!(
+                    "  History truncated after {} spends - the state below is as of coin {}, which might not be the latest.",
+                    spends,
+                    hex::encode(latest_coin_id.to_vec())
+                );
+            }
+            break;
+        }
     }
 
     if print {
+        if hidden_spends > 0 {
+            println!("  ({} earlier spends not shown)", hidden_spends);
+        }
         if let Some(latest_stream) = latest_stream {
             println!(
-                "Remaining (unclaimed) amount: {:.3}",
-                latest_stream.coin.amount as f64 / 1000.0
+                "Remaining (unclaimed) amount: {}",
+                metadata.format(latest_stream.coin.amount)
             );
             println!(
                 "Latest claim time: {} (local: {})",
@@ -301,19 +1405,154 @@ async fn sync_stream(
                     .format("%Y-%m-%d %H:%M:%S")
             );
 
-            if print_claimable {
-                let time_now = get_latest_timestamp(cli).await?;
-                let claimable = latest_stream
-                    .info
-                    .amount_to_be_paid(latest_stream.coin.amount, time_now);
-                println!("Claimable right now: {:.3} CATs", claimable as f64 / 1000.0);
+            if print_claimable && !truncated {
+                let time_now = timestamps.get(cli).await?;
+                let claimable =
+                    amount_to_be_paid(&latest_stream.info, latest_stream.coin.amount, time_now);
+                println!("Claimable right now: {}", metadata.format(claimable));
+                if latest_stream.info.end_time > time_now {
+                    let block_time = block_time.unwrap_or(AVERAGE_BLOCK_TIME);
+                    let projection = project_per_block(
+                        &latest_stream.info,
+                        latest_stream.coin.amount,
+                        time_now,
+                        block_time,
+                    );
+                    println!(
+                        "Fully vested in: {} (~{} blocks)",
+                        format_duration(latest_stream.info.end_time - time_now),
+                        projection.blocks_remaining
+                    );
+                    println!(
+                        "Vesting per block: ~{} (assuming {}s blocks)",
+                        metadata.format(projection.amount_per_block),
+                        block_time
+                    );
+                } else {
+                    println!("Fully vested.");
+                }
             }
 
-            return Ok(Some(latest_stream));
+            return Ok((Some(latest_stream), history));
         }
     }
 
-    Ok(latest_stream)
+    Ok((latest_stream, history))
+}
+
+/// A state token only describes a coin, so check that it continues `stream_id`: the asset,
+/// recipient, clawback address and end time never change over a stream's life, and later
+/// coins have a later last payment time and a smaller amount than the launch coin.
+async fn check_state_token_stream(
+    token_stream: &StreamedCat,
+    stream_id: String,
+    cli: &CoinsetClient,
+    timestamps: &mut TimestampCache,
+    testnet11: bool,
+) -> Result<(), CliError> {
+    let launch_stream = sync_stream(
+        stream_id.clone(),
+        cli,
+        timestamps,
+        get_stream_prefix(testnet11),
+        get_address_prefix(testnet11),
+        SyncOptions {
+            max_spends: Some(0),
+            ..Default::default()
+        },
+    )
+    .await?
+    .ok_or(CliError::StreamUnavailable)?;
+
+    let (launch, token) = (&launch_stream.info, &token_stream.info);
+    if launch_stream.asset_id != token_stream.asset_id
+        || launch.recipient != token.recipient
+        || launch.clawback_ph != token.clawback_ph
+        || launch.end_time != token.end_time
+        || launch.last_payment_time > token.last_payment_time
+        || launch_stream.coin.amount < token_stream.coin.amount
+    {
+        return Err(CliError::StateTokenMismatch(stream_id));
+    }
+
+    Ok(())
+}
+
+/// Walks a stream and assembles its timeline as of the latest block.
+async fn sync_timeline(
+    stream_id: String,
+    cli: &CoinsetClient,
+    timestamps: &mut TimestampCache,
+    stream_prefix: &str,
+    prefix: &str,
+) -> Result<StreamTimeline, CliError> {
+    let (latest_stream, history) = sync_stream_history(
+        stream_id,
+        cli,
+        timestamps,
+        stream_prefix.to_string(),
+        prefix.to_string(),
+        SyncOptions {
+            claim_timestamps: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    let launch = history.launch.ok_or(CliError::StreamUnavailable)?;
+
+    Ok(StreamTimeline::new(
+        &launch,
+        history.claims,
+        latest_stream,
+        timestamps.get(cli).await?,
+    ))
+}
+
+/// One-line summary of a stream for `view --compact`: remaining and claimable amounts, and
+/// when it's fully vested. Finished (fully claimed or clawed back) streams have no latest coin.
+async fn print_compact_summary(
+    stream_id: &str,
+    latest_stream: Option<&StreamedCat>,
+    cli: &CoinsetClient,
+    timestamps: &mut TimestampCache,
+    cat_metadata: &CatMetadataMap,
+) -> Result<(), CliError> {
+    let Some(latest_stream) = latest_stream else {
+        println!("{} finished", stream_id);
+        return Ok(());
+    };
+
+    let metadata = cat_metadata.get(latest_stream.asset_id);
+    let time_now = timestamps.get(cli).await?;
+    println!(
+        "{} remaining={} claimable={} end={}",
+        stream_id,
+        metadata.format(latest_stream.coin.amount),
+        metadata.format(amount_to_be_paid(
+            &latest_stream.info,
+            latest_stream.coin.amount,
+            time_now
+        )),
+        Local
+            .timestamp_opt(latest_stream.info.end_time as i64, 0)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(())
+}
+
+fn print_raw_coin_record(coin_record: &CoinRecord) {
+    println!(
+        "  [raw] coin record {}: parent_coin_info=0x{} puzzle_hash=0x{} amount={} confirmed_block_index={} spent_block_index={} coinbase={} timestamp={}",
+        hex::encode(coin_record.coin.coin_id().to_vec()),
+        hex::encode(coin_record.coin.parent_coin_info.to_vec()),
+        hex::encode(coin_record.coin.puzzle_hash.to_vec()),
+        coin_record.coin.amount,
+        coin_record.confirmed_block_index,
+        coin_record.spent_block_index,
+        coin_record.coinbase,
+        coin_record.timestamp
+    );
 }
 
 async fn wait_for_coin(
@@ -342,155 +1581,771 @@ async fn wait_for_coin(
     Ok(())
 }
 
-async fn get_latest_timestamp(cli: &CoinsetClient) -> Result<u64, CliError> {
-    let state_resp = cli
-        .get_blockchain_state()
+/// Waits until every coin in `coin_ids` has been created.
+async fn wait_for_coins(coin_ids: &[Bytes32], cli: &CoinsetClient) -> Result<(), CliError> {
+    let mut pending = coin_ids.to_vec();
+    while !pending.is_empty() {
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+        let mut still_pending = Vec::new();
+        for coin_id in pending {
+            let coin_resp = cli.get_coin_record_by_name(coin_id).await?;
+            if !coin_resp.success || coin_resp.coin_record.is_none() {
+                still_pending.push(coin_id);
+            }
+        }
+        pending = still_pending;
+    }
+
+    Ok(())
+}
+
+/// Waits for the streaming coin to be spent, or - with `--no-wait` - prints what's needed to
+/// check on the transaction later and returns immediately.
+async fn wait_for_spend(
+    cli: &CoinsetClient,
+    coin_id: Bytes32,
+    stream_id: &str,
+    no_wait: bool,
+) -> Result<(), CliError> {
+    if no_wait {
+        println!(
+            "Submitted - spent coin id: 0x{}",
+            hex::encode(coin_id.to_vec())
+        );
+        eprintln!("Run 'view {}' later to check that it confirmed.", stream_id);
+        return Ok(());
+    }
+
+    eprintln!("Waiting for transaction to be confirmed...");
+    wait_for_coin(coin_id, cli, true).await?;
+    eprintln!("Confirmed :)");
+
+    Ok(())
+}
+
+/// Block at which `coin_id` was spent, or `None` if it's still unspent. Claims and clawbacks
+/// check this first, so re-running one after an interrupted wait (or with a stale state token)
+/// doesn't build a spend that can never confirm.
+async fn get_spent_block_index(
+    cli: &CoinsetClient,
+    coin_id: Bytes32,
+) -> Result<Option<u32>, CliError> {
+    let coin_record_resp = cli
+        .get_coin_record_by_name(coin_id)
         .await
         .map_err(CliError::Reqwest)?;
-    let Some(state) = state_resp.blockchain_state else {
-        println!("Failed to get blockchain state");
-        return Err(CliError::InvalidStreamId());
+    spent_block_index(coin_record_resp)
+}
+
+fn spent_block_index(coin_record_resp: GetCoinRecordResponse) -> Result<Option<u32>, CliError> {
+    if !coin_record_resp.success {
+        return Err(CliError::CoinsetUnsuccessful(
+            coin_record_resp.error.unwrap_or_default(),
+        ));
+    }
+    Ok(coin_record_resp
+        .coin_record
+        .filter(|coin_record| coin_record.spent)
+        .map(|coin_record| coin_record.spent_block_index))
+}
+
+/// Checks that the CAT coin a confirmed claim paid to the recipient exists.
+async fn check_payout(
+    cli: &CoinsetClient,
+    payout: Option<Coin>,
+    metadata: &CatMetadata,
+) -> Result<(), CliError> {
+    let Some(payout) = payout else {
+        eprintln!("The claim paid nothing out - there is no payout to verify.");
+        return Ok(());
+    };
+
+    let coin_record_resp = cli
+        .get_coin_record_by_name(payout.coin_id())
+        .await
+        .map_err(CliError::Reqwest)?;
+    if !coin_record_resp.success {
+        return Err(CliError::CoinsetUnsuccessful(
+            coin_record_resp.error.unwrap_or_default(),
+        ));
+    }
+    if coin_record_resp.coin_record.is_none() {
+        return Err(CliError::PayoutNotFound(payout.coin_id()));
+    }
+
+    eprintln!(
+        "Payout verified: coin 0x{} holds {}",
+        hex::encode(payout.coin_id().to_vec()),
+        metadata.format(payout.amount)
+    );
+    Ok(())
+}
+
+/// Checks that the confirmed coin `coin_id` is the launched stream - a CAT of `asset_id` with
+/// the streaming inner puzzle hash and the launched amount - and returns its record.
+async fn verify_launched_coin(
+    cli: &CoinsetClient,
+    coin_id: Bytes32,
+    asset_id: Bytes32,
+    inner_puzzle_hash: Bytes32,
+    amount: u64,
+) -> Result<CoinRecord, CliError> {
+    let coin_record_resp = cli
+        .get_coin_record_by_name(coin_id)
+        .await
+        .map_err(CliError::Reqwest)?;
+    if !coin_record_resp.success {
+        return Err(CliError::CoinsetUnsuccessful(
+            coin_record_resp.error.unwrap_or_default(),
+        ));
+    }
+    let Some(coin_record) = coin_record_resp.coin_record else {
+        return Err(CliError::UnknownStreamingCoinId);
+    };
+
+    let puzzle_hash: Bytes32 = CatArgs::curry_tree_hash(asset_id, inner_puzzle_hash.into()).into();
+    if coin_record.coin.puzzle_hash != puzzle_hash {
+        return Err(CliError::LaunchMismatch(format!(
+            "puzzle hash is 0x{}, expected 0x{}",
+            hex::encode(coin_record.coin.puzzle_hash.to_vec()),
+            hex::encode(puzzle_hash.to_vec())
+        )));
+    }
+    if coin_record.coin.amount != amount {
+        return Err(CliError::LaunchMismatch(format!(
+            "amount is {}, expected {}",
+            coin_record.coin.amount, amount
+        )));
+    }
+
+    Ok(coin_record)
+}
+
+const BLOCKCHAIN_STATE_ATTEMPTS: u32 = 4;
+
+/// Calls `fetch` up to `attempts` times until it returns something, sleeping between attempts
+/// with a backoff that starts at `backoff` and doubles. Returns `None` if every attempt came
+/// back empty.
+async fn retry_with_backoff<T, F, Fut>(
+    attempts: u32,
+    mut backoff: tokio::time::Duration,
+    mut fetch: F,
+) -> Result<Option<T>, CliError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<T>, CliError>>,
+{
+    for attempt in 1..=attempts {
+        if let Some(value) = fetch().await? {
+            return Ok(Some(value));
+        }
+
+        if attempt < attempts {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    Ok(None)
+}
+
+/// Height and timestamp of the latest transaction block (the peak might not have a timestamp).
+///
+/// A missing blockchain state is retried with backoff. If it's still missing, `fallback` (a
+/// block the caller saw earlier) is returned instead. Only callers that are fine with an older
+/// time should pass one: a claim's payment time only has to be in the past, but a clawback's
+/// has to be in the future, so a stale timestamp could make it unconfirmable.
+async fn get_latest_block(
+    cli: &CoinsetClient,
+    fallback: Option<(u32, u64)>,
+) -> Result<(u32, u64), CliError> {
+    let peak = retry_with_backoff(
+        BLOCKCHAIN_STATE_ATTEMPTS,
+        tokio::time::Duration::from_secs(1),
+        || async move {
+            let state_resp = cli
+                .get_blockchain_state()
+                .await
+                .map_err(CliError::Reqwest)?;
+            Ok(state_resp.blockchain_state.map(|state| state.peak))
+        },
+    )
+    .await?;
+    let Some(mut block_record) = peak else {
+        return match fallback {
+            Some((height, timestamp)) => {
+                eprintln!(
+                    "Warning: blockchain state unavailable - using the last known transaction block (height {})",
+                    height
+                );
+                Ok((height, timestamp))
+            }
+            None => Err(CliError::BlockchainStateUnavailable),
+        };
     };
 
-    let mut block_record = state.peak;
     while block_record.timestamp.is_none() {
         let block_resp = cli
             .get_block_record_by_height(block_record.height - 1)
             .await
             .map_err(CliError::Reqwest)?;
         let Some(new_block_record) = block_resp.block_record else {
-            println!("Failed to get block record");
-            return Err(CliError::InvalidStreamId());
+            return Err(CliError::BlockRecordUnavailable);
         };
 
         block_record = new_block_record;
     }
 
-    Ok(block_record.timestamp.unwrap())
+    Ok((block_record.height, block_record.timestamp.unwrap()))
 }
 
-async fn get_public_key(
+async fn get_latest_timestamp(cli: &CoinsetClient) -> Result<u64, CliError> {
+    Ok(get_latest_block(cli, None).await?.1)
+}
+
+/// Polls until a transaction block above `block` (as returned by `get_latest_block`) is
+/// confirmed, then returns its height and timestamp. Only used before claims, so a poll that
+/// can't get the blockchain state falls back to the last block seen and simply polls again.
+async fn wait_for_next_block(
+    cli: &CoinsetClient,
+    block: (u32, u64),
+) -> Result<(u32, u64), CliError> {
+    let height = block.0;
+    let mut latest_block = block;
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+        latest_block = get_latest_block(cli, Some(latest_block)).await?;
+        if latest_block.0 > height {
+            return Ok(latest_block);
+        }
+    }
+}
+
+/// Memoizes block timestamps for the duration of a single command, since finding the
+/// latest one might require walking back several blocks and spend histories often
+/// reference the same heights.
+#[derive(Debug, Default)]
+struct TimestampCache {
+    latest_timestamp: Option<u64>,
+    block_timestamps: HashMap<u32, u64>,
+}
+
+impl TimestampCache {
+    async fn get(&mut self, cli: &CoinsetClient) -> Result<u64, CliError> {
+        if let Some(latest_timestamp) = self.latest_timestamp {
+            return Ok(latest_timestamp);
+        }
+
+        let latest_timestamp = get_latest_timestamp(cli).await?;
+        self.latest_timestamp = Some(latest_timestamp);
+        Ok(latest_timestamp)
+    }
+
+    /// Timestamp of the transaction block at `height` (e.g., a coin's spent_block_index).
+    async fn get_block_timestamp(
+        &mut self,
+        cli: &CoinsetClient,
+        height: u32,
+    ) -> Result<u64, CliError> {
+        if let Some(timestamp) = self.block_timestamps.get(&height) {
+            return Ok(*timestamp);
+        }
+
+        let block_resp = cli
+            .get_block_record_by_height(height)
+            .await
+            .map_err(CliError::Reqwest)?;
+        let Some(timestamp) = block_resp.block_record.and_then(|record| record.timestamp) else {
+            return Err(CliError::BlockRecordUnavailable);
+        };
+
+        self.block_timestamps.insert(height, timestamp);
+        Ok(timestamp)
+    }
+}
+
+// Rough upper bound for the cost of a claim/clawback bundle (lead coin + streamed CAT spend)
+const ESTIMATED_SPEND_COST: u64 = 50_000_000;
+
+async fn get_mempool_min_fee(cli: &CoinsetClient) -> Result<u64, CliError> {
+    let state_resp = cli
+        .get_blockchain_state()
+        .await
+        .map_err(CliError::Reqwest)?;
+    let Some(state) = state_resp.blockchain_state else {
+        return Err(CliError::BlockchainStateUnavailable);
+    };
+
+    // cost_5000000 is the minimum fee per cost unit required to enter a full mempool
+    let min_fee_per_cost = state.mempool_min_fees.cost_5000000 as f64;
+    Ok((min_fee_per_cost * ESTIMATED_SPEND_COST as f64).ceil() as u64)
+}
+
+const DERIVATION_BATCH_SIZE: u64 = 1000;
+
+/// Scans derivations of one kind (continuing from the cache) until `address` is found or
+/// `limit` derivations have been scanned. Returns the hex-encoded public key.
+async fn scan_derivations(
     cli: &SageClient,
+    derivations: &mut CachedDerivations,
     address: &str,
-    max_derivations: u64,
     hardened: bool,
-) -> Result<PublicKey, CliError> {
-    let mut public_key: Option<PublicKey> = None;
-    for i in (0..max_derivations).step_by(1000) {
+    limit: u64,
+) -> Result<Option<String>, CliError> {
+    let keyspace = if hardened { "hardened" } else { "unhardened" };
+    if let Some(public_key) = derivations.public_keys.get(address) {
+        eprintln!("Found key in the cached {} derivations", keyspace);
+        return Ok(Some(public_key.clone()));
+    }
+
+    let mut public_key = None;
+    while public_key.is_none() && derivations.scanned < limit {
         let derivation_resp = cli
             .get_derivations(GetDerivations {
-                offset: i as u32,
-                limit: 1000,
+                offset: derivations.scanned as u32,
+                limit: DERIVATION_BATCH_SIZE as u32,
                 hardened,
             })
             .await?;
 
+        let count = derivation_resp.derivations.len() as u64;
         for derivation in derivation_resp.derivations {
             if derivation.address == address {
-                let pubkey_bytes = hex::decode(derivation.public_key).unwrap();
-                let pubkey_bytes: [u8; 48] = pubkey_bytes.try_into().unwrap();
-                public_key = Some(PublicKey::from_bytes(&pubkey_bytes).unwrap());
-                break;
+                eprintln!(
+                    "Found key at {} derivation index {}",
+                    keyspace, derivation.index
+                );
+                public_key = Some(derivation.public_key.clone());
             }
+            derivations
+                .public_keys
+                .insert(derivation.address, derivation.public_key);
         }
-    }
 
-    let Some(public_key) = public_key else {
-        println!("Failed to find public key");
-        return Err(CliError::InvalidStreamId());
-    };
+        derivations.scanned += count;
+        // Sage only returns derivations it has already generated
+        if count < DERIVATION_BATCH_SIZE {
+            break;
+        }
+    }
 
     Ok(public_key)
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn generate_spend_bundle(
-    sage_client: &SageClient,
-    latest_streamed_coin: StreamedCat,
-    public_key: PublicKey,
-    p2_puzzle_hash: Bytes32,
-    p2_address: &str,
-    fee: String,
-    claim_time: u64,
-    clawback: bool,
-) -> Result<Bytes32, CliError> {
-    let mut ctx = SpendContext::new();
-    let p2 = StandardLayer::new(public_key);
-    let p2_puzzle_ptr = p2.construct_puzzle(&mut ctx)?;
-    if ctx.tree_hash(p2_puzzle_ptr) != p2_puzzle_hash.into() {
-        eprintln!("Wallet is using non-standard puzzle :(");
-        return Err(CliError::InvalidStreamId());
-    }
+/// Finds the public key for `address` among the wallet's derivations. With `auto`, both
+/// keyspaces are searched - unhardened first - raising the gap limit by one batch at a time
+/// until the key is found or `max_derivations` is reached.
+async fn get_public_key(
+    cli: &SageClient,
+    address: &str,
+    max_derivations: u64,
+    hardened: bool,
+    auto: bool,
+    refresh_derivations: bool,
+) -> Result<PublicKey, CliError> {
+    let Some(key) = cli.get_key(GetKey { fingerprint: None }).await?.key else {
+        return Err(CliError::NoActiveKey);
+    };
 
-    let initial_send = sage_client
-        .send_xch(SendXch {
-            address: p2_address.to_string(),
-            amount: Amount::Number(0),
-            fee: Amount::Number(parse_amount(fee, false)?),
-            memos: None,
-            auto_submit: false,
-        })
-        .await?;
+    let mut cache = DerivationCache::load(key.fingerprint, &Address::decode(address)?.prefix);
+    if refresh_derivations {
+        cache.clear();
+    }
 
-    for spend in initial_send.coin_spends {
-        let parent_coin_info: [u8; 32] = hex::decode(spend.coin.parent_coin_info.replace("0x", ""))
-            .map_err(CliError::HexDecodingFailed)?
-            .try_into()
-            .unwrap();
-        let puzzle_hash: [u8; 32] = hex::decode(spend.coin.puzzle_hash.replace("0x", ""))
-            .map_err(CliError::HexDecodingFailed)?
-            .try_into()
-            .unwrap();
-        let coin = Coin::new(
-            Bytes32::from(parent_coin_info),
-            Bytes32::from(puzzle_hash),
-            match spend.coin.amount {
-                Amount::Number(amount) => amount,
-                Amount::String(amount) => amount.parse::<u64>().unwrap(),
-            },
-        );
+    let keyspaces: &[bool] = if auto { &[false, true] } else { &[hardened] };
+    let mut limit = if auto {
+        DERIVATION_BATCH_SIZE.min(max_derivations)
+    } else {
+        max_derivations
+    };
+    let mut public_key = None;
+    'search: loop {
+        for &hardened in keyspaces {
+            public_key =
+                scan_derivations(cli, cache.derivations(hardened), address, hardened, limit)
+                    .await?;
+            if public_key.is_some() {
+                break 'search;
+            }
+        }
 
-        let puzzle_reveal: Vec<u8> = hex::decode(spend.puzzle_reveal.replace("0x", "0"))
-            .map_err(CliError::HexDecodingFailed)?;
-        let solution: Vec<u8> =
-            hex::decode(spend.solution.replace("0x", "0")).map_err(CliError::HexDecodingFailed)?;
+        if limit >= max_derivations {
+            break;
+        }
+        limit = (limit + DERIVATION_BATCH_SIZE).min(max_derivations);
+    }
 
-        ctx.insert(CoinSpend {
-            coin,
-            puzzle_reveal: Program::from_bytes(&puzzle_reveal).unwrap(),
-            solution: Program::from_bytes(&solution).unwrap(),
-        });
+    if let Err(err) = cache.save() {
+        eprintln!("Warning: could not save derivation cache: {}", err);
     }
 
-    let mut lead_coin_parent: Option<Bytes32> = None;
-    for input in initial_send.summary.inputs {
-        let AssetKind::Xch = input.kind else {
-            continue;
-        };
+    public_key
+        .and_then(|public_key| hex::decode(public_key).ok())
+        .and_then(|bytes| <[u8; 48]>::try_from(bytes).ok())
+        .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+        .ok_or(CliError::PublicKeyNotFound(max_derivations, auto))
+}
 
-        if !input
-            .outputs
-            .iter()
-            .any(|c| c.amount == Amount::Number(0) && c.address == p2_address)
-        {
-            continue;
-        };
+/// Parses a `--recipient-pubkey` and returns the key the recipient's standard puzzle is curried
+/// with - either the given key itself or its synthetic key.
+fn recipient_key_from_hex(public_key: &str, recipient: Bytes32) -> Result<PublicKey, CliError> {
+    let bytes: [u8; 48] = hex::decode(public_key.strip_prefix("0x").unwrap_or(public_key))?
+        .try_into()
+        .map_err(|_| CliError::InvalidPublicKey)?;
+    let public_key = PublicKey::from_bytes(&bytes).map_err(|_| CliError::InvalidPublicKey)?;
 
-        let lead_coin_parent_b32: [u8; 32] = hex::decode(input.coin_id.replace("0x", ""))?
-            .try_into()
-            .unwrap();
-        lead_coin_parent = Some(Bytes32::from(lead_coin_parent_b32));
+    [public_key, public_key.derive_synthetic()]
+        .into_iter()
+        .find(|public_key| StandardArgs::curry_tree_hash(*public_key) == recipient.into())
+        .ok_or(CliError::RecipientKeyMismatch)
+}
+
+/// With a limit of 0 no derivation would ever be searched, so the key could never be found.
+fn check_max_derivations(max_derivations: u64) -> Result<(), CliError> {
+    if max_derivations == 0 {
+        return Err(CliError::InvalidMaxDerivations);
     }
 
-    let Some(lead_coin_parent) = lead_coin_parent else {
-        println!("Failed to find lead coin parent");
-        return Err(CliError::InvalidStreamId());
-    };
+    Ok(())
+}
 
-    let lead_coin = Coin::new(lead_coin_parent, p2_puzzle_hash, 0);
+/// Checks that Sage is on the network `address` belongs to, by comparing its receive address
+/// prefix. Otherwise, Sage would fund the transaction with coins from the wrong network.
+async fn check_sage_network(sage_client: &SageClient, address: &str) -> Result<(), CliError> {
+    let sync_status = sage_client.get_sync_status(GetSyncStatus {}).await?;
+    let sage_prefix = Address::decode(&sync_status.receive_address)?.prefix;
+    let expected_prefix = Address::decode(address)?.prefix;
+    if sage_prefix != expected_prefix {
+        return Err(CliError::SageNetworkMismatch(sage_prefix, expected_prefix));
+    }
 
-    let message_to_send = Bytes::new(u64_to_bytes(claim_time));
-    let coin_id_ptr = ctx.alloc(&latest_streamed_coin.coin.coin_id())?;
+    Ok(())
+}
+
+/// Checks that the wallet holds at least `cat_amount` of `asset_id` and `fee` XCH, so a launch
+/// fails before the confirmation prompt instead of when Sage builds the transaction.
+async fn check_balances(
+    sage_client: &SageClient,
+    asset_id: Bytes32,
+    cat_amount: u64,
+    fee: u64,
+    metadata: &CatMetadata,
+) -> Result<(), CliError> {
+    let cat_balance = match sage_client
+        .get_cat(GetCat {
+            asset_id: hex::encode(asset_id.to_vec()),
+        })
+        .await?
+        .cat
+    {
+        Some(cat) => amount_to_u64(&cat.balance)?,
+        None => 0,
+    };
+    if cat_balance < cat_amount {
+        return Err(CliError::InsufficientBalance(
+            "CAT".to_string(),
+            metadata.format(cat_balance),
+            metadata.format(cat_amount),
+        ));
+    }
+
+    let xch_balance = amount_to_u64(&sage_client.get_sync_status(GetSyncStatus {}).await?.balance)?;
+    if xch_balance < fee {
+        return Err(CliError::InsufficientBalance(
+            "XCH".to_string(),
+            format!("{:.12}", xch_balance as f64 / 1_000_000_000_000.0),
+            format!("{:.12}", fee as f64 / 1_000_000_000_000.0),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Creates the Sage RPC client. Commands should create it once and pass it around, since
+/// it holds the parsed TLS identity and a pooled connection.
+fn connect_sage() -> Result<SageClient, CliError> {
+    SageClient::new().map_err(|e| {
+        eprintln!("Failed to create Sage client: {}", e);
+        CliError::HomeDirectoryNotFound
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ConfirmationSettings {
+    /// `--yes`
+    skip: bool,
+    /// `--confirm-timeout`, in seconds
+    timeout: Option<u64>,
+}
+
+// Set once from the global flags, before any prompt can be shown
+static CONFIRMATION_SETTINGS: OnceLock<ConfirmationSettings> = OnceLock::new();
+
+/// Shows `message` and waits for Enter. With `--confirm-timeout`, no input in time aborts the
+/// operation - it's never taken as a confirmation.
+fn wait_for_confirmation(message: &str) -> Result<(), CliError> {
+    let settings = CONFIRMATION_SETTINGS.get().copied().unwrap_or_default();
+    eprintln!("{message}");
+    if settings.skip {
+        eprintln!("(confirmed by --yes)");
+        return Ok(());
+    }
+
+    let Some(timeout) = settings.timeout else {
+        let _ = std::io::stdin().read_line(&mut String::new());
+        return Ok(());
+    };
+
+    // The blocking read can't be interrupted, so it runs on its own thread, which is simply
+    // left behind (and ends with the process) if the timeout passes
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = std::io::stdin().read_line(&mut String::new());
+        let _ = sender.send(());
+    });
+    receiver
+        .recv_timeout(std::time::Duration::from_secs(timeout))
+        .map_err(|_| CliError::Aborted(format_duration(timeout)))
+}
+
+/// Latest payment time a claim can use right now: the stream's end, or just below the
+/// latest block timestamp if the stream hasn't fully vested yet.
+fn max_claim_time(info: &StreamingPuzzleInfo, latest_timestamp: u64) -> u64 {
+    (latest_timestamp - 1).min(info.end_time)
+}
+
+/// `sync-claim` skips claims that aren't worth their fee: unless `claim_amount` reaches
+/// `min_claim_value` or `min_interval` has passed since the last payment, returns the earliest
+/// time either will be met. The final claim of a stream is never skipped, since nothing more
+/// vests after it.
+fn next_worthwhile_claim_time(
+    stream: &StreamedCat,
+    claim_time: u64,
+    claim_amount: u64,
+    min_claim_value: Option<u64>,
+    min_interval: Option<u64>,
+) -> Option<u64> {
+    if claim_time >= stream.info.end_time || (min_claim_value.is_none() && min_interval.is_none()) {
+        return None;
+    }
+
+    let value_time = min_claim_value.map(|min_claim_value| {
+        payment_time_for_amount(&stream.info, stream.coin.amount, min_claim_value)
+            .unwrap_or(stream.info.end_time)
+    });
+    let interval_time =
+        min_interval.map(|min_interval| stream.info.last_payment_time + min_interval);
+    if min_claim_value.is_some_and(|min_claim_value| claim_amount >= min_claim_value)
+        || interval_time.is_some_and(|interval_time| claim_time >= interval_time)
+    {
+        return None;
+    }
+
+    [value_time, interval_time]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|next_claim_time| next_claim_time.min(stream.info.end_time))
+}
+
+/// The streaming puzzle only accepts payment times between the last payment and the end time.
+fn check_claim_window(claim_time: u64, info: &StreamingPuzzleInfo) -> Result<(), CliError> {
+    if claim_time < info.last_payment_time || claim_time > info.end_time {
+        return Err(CliError::ClaimTimeOutsideWindow(
+            claim_time,
+            info.last_payment_time,
+            info.end_time,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that the puzzle accepts `claim_time`. Claims also assert `ASSERT_SECONDS_ABSOLUTE
+/// claim_time`, so a claim time that isn't strictly below the latest block timestamp makes the
+/// spend sit in the mempool until that time passes. Fail early instead of waiting for a
+/// confirmation that may never come.
+fn check_claim_time(
+    claim_time: u64,
+    info: &StreamingPuzzleInfo,
+    latest_timestamp: u64,
+) -> Result<(), CliError> {
+    check_claim_window(claim_time, info)?;
+    if claim_time >= latest_timestamp {
+        return Err(CliError::ClaimTimeInFuture(claim_time, latest_timestamp));
+    }
+
+    Ok(())
+}
+
+/// Puts the bundle in a fixed order - Sage's fee spends, then the lead (message) coin, then the
+/// streamed CAT - independently of how `SpendContext` happens to store them. Also makes sure
+/// both the lead coin and the streamed CAT are spent, and that no coin is spent twice.
+fn order_spends(
+    coin_spends: Vec<CoinSpend>,
+    lead_coin_id: Bytes32,
+    streamed_coin_id: Bytes32,
+) -> Result<Vec<CoinSpend>, CliError> {
+    let mut coin_ids = HashSet::new();
+    if !coin_spends
+        .iter()
+        .all(|coin_spend| coin_ids.insert(coin_spend.coin.coin_id()))
+    {
+        return Err(CliError::InvalidSpendBundle(
+            "a coin is spent more than once".to_string(),
+        ));
+    }
+    if !coin_ids.contains(&lead_coin_id) || !coin_ids.contains(&streamed_coin_id) {
+        return Err(CliError::InvalidSpendBundle(
+            "lead coin or streamed CAT spend missing".to_string(),
+        ));
+    }
+
+    let mut coin_spends = coin_spends;
+    coin_spends.sort_by_key(|coin_spend| {
+        let coin_id = coin_spend.coin.coin_id();
+        if coin_id == streamed_coin_id {
+            2
+        } else if coin_id == lead_coin_id {
+            1
+        } else {
+            0
+        }
+    });
+
+    Ok(coin_spends)
+}
+
+/// Where the fee of a claim/clawback comes from: the wallet's XCH coins spent to pay it (and
+/// to create the lead coin), and the change sent back to the wallet.
+struct FeeDetails {
+    fee: u64,
+    fee_coins: Vec<(String, u64)>,
+    change: u64,
+}
+
+impl FeeDetails {
+    fn from_summary(summary: &TransactionSummary) -> Result<Self, CliError> {
+        let mut fee_coins = Vec::new();
+        let mut change = 0;
+        for input in &summary.inputs {
+            let AssetKind::Xch = input.kind else {
+                continue;
+            };
+
+            fee_coins.push((input.coin_id.clone(), amount_to_u64(&input.amount)?));
+            for output in &input.outputs {
+                if output.receiving {
+                    change += amount_to_u64(&output.amount)?;
+                }
+            }
+        }
+
+        Ok(Self {
+            fee: amount_to_u64(&summary.fee)?,
+            fee_coins,
+            change,
+        })
+    }
+
+    fn print(&self) {
+        eprintln!(
+            "Fee: {:.12} XCH, paid from:",
+            self.fee as f64 / 1_000_000_000_000.0
+        );
+        for (coin_id, amount) in &self.fee_coins {
+            eprintln!(
+                "  {} ({:.12} XCH)",
+                coin_id,
+                *amount as f64 / 1_000_000_000_000.0
+            );
+        }
+        eprintln!(
+            "Change returned to your wallet: {:.12} XCH",
+            self.change as f64 / 1_000_000_000_000.0
+        );
+    }
+}
+
+/// Builds, signs (via Sage) and submits the claim/clawback bundle. Uses its own `SpendContext`,
+/// since the collected spends are taken out of it for signing.
+#[allow(clippy::too_many_arguments)]
+async fn generate_spend_bundle(
+    sage_client: &SageClient,
+    cli: &CoinsetClient,
+    latest_streamed_coin: StreamedCat,
+    public_key: PublicKey,
+    p2_puzzle_hash: Bytes32,
+    p2_address: &str,
+    fee: u64,
+    claim_time: u64,
+    clawback: bool,
+    allow_low_fee: bool,
+    skip_confirmation: bool,
+    network: NetworkConstants,
+) -> Result<Bytes32, CliError> {
+    let mut ctx = SpendContext::new();
+    let p2 = StandardLayer::new(public_key);
+    let p2_puzzle_ptr = p2.construct_puzzle(&mut ctx)?;
+    if ctx.tree_hash(p2_puzzle_ptr) != p2_puzzle_hash.into() {
+        return Err(CliError::NonStandardPuzzle);
+    }
+
+    check_sage_network(sage_client, p2_address).await?;
+    let initial_send = sage_client
+        .send_xch(SendXch {
+            address: p2_address.to_string(),
+            amount: Amount::Number(0),
+            fee: Amount::Number(fee),
+            memos: None,
+            auto_submit: false,
+        })
+        .await?;
+
+    for spend in &initial_send.coin_spends {
+        ctx.insert(coin_spend_from_json(spend)?);
+    }
+    let fee_details = FeeDetails::from_summary(&initial_send.summary)?;
+
+    let mut lead_coin_parent: Option<Bytes32> = None;
+    for input in initial_send.summary.inputs {
+        let AssetKind::Xch = input.kind else {
+            continue;
+        };
+
+        if !input
+            .outputs
+            .iter()
+            .any(|c| matches!(amount_to_u64(&c.amount), Ok(0)) && c.address == p2_address)
+        {
+            continue;
+        };
+
+        let lead_coin_parent_b32: [u8; 32] = hex::decode(input.coin_id.replace("0x", ""))?
+            .try_into()
+            .map_err(|_| {
+                client::ClientError::InvalidResponse(format!(
+                    "Invalid coin id: {:?}",
+                    input.coin_id
+                ))
+            })?;
+        lead_coin_parent = Some(Bytes32::from(lead_coin_parent_b32));
+    }
+
+    let Some(lead_coin_parent) = lead_coin_parent else {
+        return Err(CliError::LeadCoinNotFound);
+    };
+
+    // The streaming puzzle authorizes spends via RECEIVE_MESSAGE mode 23, which pins the
+    // sender's puzzle hash to RECIPIENT (or CLAWBACK_PH for clawbacks). The message therefore
+    // has to come from a coin locked by that exact puzzle hash - using an unrelated coin is
+    // not possible without changing the puzzle, so the recipient's p2 puzzle is always revealed.
+    let lead_coin = Coin::new(lead_coin_parent, p2_puzzle_hash, 0);
+
+    let message_to_send = Bytes::new(u64_to_bytes(claim_time));
+    let coin_id_ptr = ctx.alloc(&latest_streamed_coin.coin.coin_id())?;
     p2.spend(
         &mut ctx,
         lead_coin,
@@ -498,12 +2353,29 @@ async fn generate_spend_bundle(
     )?;
     latest_streamed_coin.spend(&mut ctx, claim_time, clawback)?;
 
-    println!("Spend bundle ready. Last confirmation - press 'Enter' to proceed");
-    let _ = std::io::stdin().read_line(&mut String::new());
+    if !allow_low_fee {
+        let min_fee = get_mempool_min_fee(cli).await?;
+        if fee < min_fee {
+            eprintln!(
+                "Warning: fee of {:.12} XCH is below the current mempool minimum of ~{:.12} XCH - the transaction might not get confirmed. Use --allow-low-fee to suppress this warning.",
+                fee as f64 / 1_000_000_000_000.0,
+                min_fee as f64 / 1_000_000_000_000.0
+            );
+        }
+    }
+
+    fee_details.print();
+    if !skip_confirmation {
+        wait_for_confirmation("Spend bundle ready. Last confirmation - press 'Enter' to proceed")?;
+    }
 
+    let coin_spends = order_spends(
+        ctx.take(),
+        lead_coin.coin_id(),
+        latest_streamed_coin.coin.coin_id(),
+    )?;
     let sign_request = SignCoinSpends {
-        coin_spends: ctx
-            .take()
+        coin_spends: coin_spends
             .iter()
             .map(|c| CoinSpendJson {
                 coin: CoinJson {
@@ -518,18 +2390,116 @@ async fn generate_spend_bundle(
                 solution: format!("0x{}", hex::encode(c.solution.to_vec())),
             })
             .collect(),
-        auto_submit: true,
+        auto_submit: false,
         partial: false,
     };
 
-    let _ = sage_client.sign_coin_spends(sign_request).await?;
+    // Check Sage's signature before anything is submitted
+    let signed = sage_client.sign_coin_spends(sign_request).await?;
+    let (signed_coin_spends, aggregated_signature) = spend_bundle_from_json(&signed.spend_bundle)?;
+    assemble_and_verify(signed_coin_spends, aggregated_signature, network)?;
+    sage_client
+        .submit_transaction(SubmitTransaction {
+            spend_bundle: signed.spend_bundle,
+        })
+        .await?;
 
     Ok(latest_streamed_coin.coin.coin_id())
 }
 
+#[allow(clippy::too_many_arguments)]
+async fn submit_claim(
+    sage_client: &SageClient,
+    cli: &CoinsetClient,
+    latest_streamed_coin: StreamedCat,
+    claim_time: u64,
+    fee: u64,
+    testnet11: bool,
+    hardened: bool,
+    auto: bool,
+    max_derivations: u64,
+    refresh_derivations: bool,
+    recipient_public_key: Option<PublicKey>,
+    allow_low_fee: bool,
+    skip_confirmation: bool,
+    network: NetworkConstants,
+) -> Result<Bytes32, CliError> {
+    let recipient = latest_streamed_coin.info.recipient;
+    let recipient_address = Address::new(recipient, get_address_prefix(testnet11)).encode()?;
+    let public_key = if let Some(public_key) = recipient_public_key {
+        public_key
+    } else {
+        eprintln!(
+            "Searching for key associated with address: {}",
+            recipient_address
+        );
+
+        get_public_key(
+            sage_client,
+            &recipient_address,
+            max_derivations,
+            hardened,
+            auto,
+            refresh_derivations,
+        )
+        .await?
+    };
+
+    eprintln!("Building spend bundle...");
+    generate_spend_bundle(
+        sage_client,
+        cli,
+        latest_streamed_coin,
+        public_key,
+        recipient,
+        &recipient_address,
+        fee,
+        claim_time,
+        false,
+        allow_low_fee,
+        skip_confirmation,
+        network,
+    )
+    .await
+}
+
 #[tokio::main]
-async fn main() -> Result<(), CliError> {
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("Error: {err}");
+        std::process::exit(err.exit_code());
+    }
+}
+
+async fn run() -> Result<(), CliError> {
     let args = Cli::parse();
+    let confirm_timeout = args
+        .confirm_timeout
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?;
+    CONFIRMATION_SETTINGS
+        .set(ConfirmationSettings {
+            skip: args.yes,
+            timeout: confirm_timeout,
+        })
+        .expect("confirmation settings are only set once");
+    let coinset_url = args.coinset_url;
+    let cat_metadata = CatMetadataMap::load(args.cat_metadata.as_deref())?;
+    let genesis_challenge = args
+        .genesis_challenge
+        .map(|genesis_challenge| {
+            hex::decode(
+                genesis_challenge
+                    .strip_prefix("0x")
+                    .unwrap_or(&genesis_challenge),
+            )
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .map(Bytes32::new)
+            .ok_or(CliError::InvalidGenesisChallenge)
+        })
+        .transpose()?;
 
     match args.command {
         Commands::Launch {
@@ -540,58 +2510,109 @@ async fn main() -> Result<(), CliError> {
             recipient,
             clawback_address,
             fee,
+            fee_mojos,
             testnet11,
+            json,
+            allow_clawback_to_recipient,
+            allow_past_end,
+            no_wait,
+            skip_balance_check,
         } => {
-            let asset_id = hex::decode(asset_id).map_err(|_| CliError::InvalidAssetId)?;
+            let fee = parse_fee(fee, fee_mojos)?;
+            let asset_id: [u8; 32] = cat_metadata.resolve_asset_id(&asset_id)?.into();
 
-            let client = SageClient::new().map_err(|e| {
-                eprintln!("Failed to create client: {}", e);
-                CliError::HomeDirectoryNotFound
-            })?;
+            let client = connect_sage()?;
 
-            let recipient_puzzle_hash = Address::decode(&recipient)?.puzzle_hash;
+            let recipient_puzzle_hash = decode_address(&recipient, testnet11)?;
             let clawback_ph: Option<Bytes32> = if clawback_address == "none" {
                 None
             } else {
-                Some(Address::decode(&clawback_address)?.puzzle_hash)
+                Some(decode_address(&clawback_address, testnet11)?)
             };
             let cat_amount = parse_amount(amount, true)?;
+            if clawback_ph == Some(recipient_puzzle_hash) {
+                eprintln!("{}", CLAWBACK_IS_RECIPIENT_WARNING);
+                if !allow_clawback_to_recipient {
+                    wait_for_confirmation(
+                        "Press Enter to launch anyway (or use --allow-clawback-to-recipient to skip this check)...",
+                    )?;
+                }
+            }
+            validate_time_window(
+                start_timestamp,
+                end_timestamp,
+                Utc::now().timestamp() as u64,
+            )?;
 
-            let asset_id: [u8; 32] = asset_id.try_into().map_err(|_| CliError::InvalidAssetId)?;
-            let target_inner_puzzle_hash = StreamPuzzle2ndCurryArgs::curry_tree_hash(
-                recipient_puzzle_hash,
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let latest_timestamp = get_latest_timestamp(&cli).await?;
+            if end_timestamp < latest_timestamp {
+                eprintln!(
+                    "Warning: the end time is before the latest block time ({} - local: {}), so the recipient will be able to claim everything immediately.",
+                    latest_timestamp,
+                    Local
+                        .timestamp_opt(latest_timestamp as i64, 0)
+                        .unwrap()
+                        .format("%Y-%m-%d %H:%M:%S")
+                );
+                if !allow_past_end {
+                    wait_for_confirmation(
+                        "Press Enter to launch anyway (or use --allow-past-end to skip this check)...",
+                    )?;
+                }
+            }
+
+            if !skip_balance_check {
+                check_balances(
+                    &client,
+                    Bytes32::new(asset_id),
+                    cat_amount,
+                    fee,
+                    &cat_metadata.get(Bytes32::new(asset_id)),
+                )
+                .await?;
+            }
+            let params = StreamParameters {
+                recipient: recipient_puzzle_hash,
                 clawback_ph,
-                end_timestamp,
-                start_timestamp,
-            );
+                start_time: start_timestamp,
+                end_time: end_timestamp,
+            };
+            let target_inner_puzzle_hash = params.inner_puzzle_hash();
 
-            println!("You're about to start streaming a CAT to {}", recipient);
-            println!("Note: Sage RPC should be running on port 9257\n");
-            println!("Please note that the CAT can only be clawed back by the clawback address. Please ensure the details below are correct.");
-            println!("Asset ID: {}", hex::encode(asset_id));
-            println!("Amount: {:.3}", cat_amount as f64 / 1000.0);
-            println!(
+            eprintln!("You're about to start streaming a CAT to {}", recipient);
+            eprintln!("Note: Sage RPC should be running on port 9257\n");
+            eprintln!("Please note that the CAT can only be clawed back by the clawback address. Please ensure the details below are correct.");
+            eprintln!("Asset ID: {}", hex::encode(asset_id));
+            eprintln!(
+                "Amount: {}",
+                cat_metadata.get(Bytes32::new(asset_id)).format(cat_amount)
+            );
+            eprintln!(
                 "Start Time: {}",
                 Local
                     .timestamp_opt(start_timestamp as i64, 0)
                     .unwrap()
                     .format("%Y-%m-%d %H:%M:%S")
             );
-            println!(
+            eprintln!(
                 "End Time: {}",
                 Local
                     .timestamp_opt(end_timestamp as i64, 0)
                     .unwrap()
                     .format("%Y-%m-%d %H:%M:%S")
             );
-            println!(
-                "Fee: {:.12}",
-                parse_amount(fee.clone(), false)? as f64 / 1_000_000_000_000.0
+            eprintln!(
+                "Duration: {}",
+                format_duration(end_timestamp.saturating_sub(start_timestamp))
             );
-            println!("Mainnet?: {}", !testnet11);
+            eprintln!("Fee: {:.12}", fee as f64 / 1_000_000_000_000.0);
+            eprintln!("Mainnet?: {}", !testnet11);
 
-            println!("Press Enter to continue...");
-            let _ = std::io::stdin().read_line(&mut String::new());
+            let launch_hints = params.to_hints();
+            print_launch_memos(&launch_hints, &get_address_prefix(testnet11))?;
+
+            wait_for_confirmation("Press Enter to continue...")?;
 
             let streaming_cat_address = Address::new(
                 target_inner_puzzle_hash.into(),
@@ -599,247 +2620,1559 @@ async fn main() -> Result<(), CliError> {
             )
             .encode()?;
 
-            println!("Sending CAT...");
+            let memos: Vec<String> = launch_hints
+                .iter()
+                .map(|b| hex::encode(b.to_vec()))
+                .collect();
+
+            check_sage_network(&client, &streaming_cat_address).await?;
+            eprintln!("Sending CAT...");
             let send_cat_request = SendCat {
                 asset_id: hex::encode(asset_id),
                 address: streaming_cat_address.clone(),
                 amount: Amount::Number(cat_amount),
-                fee: Amount::Number(parse_amount(fee, false)?),
-                memos: Some(
-                    StreamingPuzzleInfo::new(
-                        Bytes32::new(recipient_puzzle_hash.into()),
-                        clawback_ph,
-                        end_timestamp,
-                        start_timestamp,
-                    )
-                    .get_launch_hints()
-                    .iter()
-                    .map(|b| hex::encode(b.to_vec()))
-                    .collect(),
-                ),
+                fee: Amount::Number(fee),
+                memos: Some(memos.clone()),
                 include_hint: false,
                 auto_submit: true,
             };
 
             let response = client.send_cat(send_cat_request).await?;
 
-            let mut streaming_coin_id: Option<String> = None;
-            let actual_asset_id = asset_id;
-            for coin in response.summary.inputs {
-                if let AssetKind::Cat { asset_id, .. } = coin.kind {
-                    if asset_id.replace("0x", "") != hex::encode(actual_asset_id) {
-                        continue;
-                    }
-                } else {
-                    continue;
+            let streaming_coin_id =
+                find_streaming_coin_id(&response, asset_id, &streaming_cat_address, cat_amount)?;
+            let stream_id = StreamId(streaming_coin_id).encode(&get_stream_prefix(testnet11))?;
+
+            if json {
+                let launch_result = LaunchResult {
+                    stream_id,
+                    streaming_coin_id: format!("0x{}", hex::encode(streaming_coin_id.to_vec())),
+                    streaming_cat_address,
+                    inner_puzzle_hash: format!(
+                        "0x{}",
+                        hex::encode(Bytes32::from(target_inner_puzzle_hash).to_vec())
+                    ),
+                    memos,
+                };
+                println!("{}", serde_json::to_string_pretty(&launch_result)?);
+            } else {
+                println!(
+                    "Streaming coin id: 0x{}",
+                    hex::encode(streaming_coin_id.to_vec())
+                );
+                println!("Stream id: {}", stream_id);
+            }
+            if no_wait {
+                if !json {
+                    eprintln!(
+                        "Submitted - run 'view {}' later to check that it confirmed.",
+                        stream_id
+                    );
                 }
+                return Ok(());
+            }
+            if !json {
+                eprintln!("Waiting for mempool item to be confirmed...");
+            }
 
-                for output in coin.outputs {
-                    if !output.receiving && output.address == streaming_cat_address {
-                        streaming_coin_id = Some(output.coin_id.clone());
-                        break;
-                    }
+            wait_for_coin(streaming_coin_id, &cli, false).await?;
+            let coin_record = verify_launched_coin(
+                &cli,
+                streaming_coin_id,
+                Bytes32::new(asset_id),
+                target_inner_puzzle_hash.into(),
+                cat_amount,
+            )
+            .await?;
+            if !json {
+                eprintln!(
+                    "Confimed! :) Streaming coin 0x{} holds {} (puzzle hash 0x{}, block {})",
+                    hex::encode(streaming_coin_id.to_vec()),
+                    cat_metadata
+                        .get(Bytes32::new(asset_id))
+                        .format(coin_record.coin.amount),
+                    hex::encode(coin_record.coin.puzzle_hash.to_vec()),
+                    coin_record.confirmed_block_index
+                );
+            }
+        }
+        Commands::LaunchBatch {
+            asset_id,
+            file,
+            total,
+            fee,
+            fee_mojos,
+            testnet11,
+            json,
+            allow_clawback_to_recipient,
+            allow_past_end,
+            no_wait,
+            skip_balance_check,
+            allow_duplicates,
+        } => {
+            let fee = parse_fee(fee, fee_mojos)?;
+            let asset_id: [u8; 32] = cat_metadata.resolve_asset_id(&asset_id)?.into();
+            let streams = read_batch_file(&file, testnet11, allow_duplicates)?;
+
+            let now = Utc::now().timestamp() as u64;
+            for stream in &streams {
+                validate_time_window(stream.start_timestamp, stream.end_timestamp, now)?;
+            }
+            let sum = streams.iter().map(|stream| stream.amount).sum::<u64>();
+            if let Some(total) = total {
+                let total = parse_amount(total, true)?;
+                if total != sum {
+                    return Err(CliError::BatchTotalMismatch(total, sum));
                 }
+            }
 
-                if streaming_coin_id.is_some() {
-                    break;
+            let client = connect_sage()?;
+            if !skip_balance_check {
+                check_balances(
+                    &client,
+                    Bytes32::new(asset_id),
+                    sum,
+                    fee,
+                    &cat_metadata.get(Bytes32::new(asset_id)),
+                )
+                .await?;
+            }
+            for (index, stream) in streams.iter().enumerate() {
+                if stream.clawback_ph == Some(stream.recipient_puzzle_hash) {
+                    eprintln!("#{}: {}", index + 1, CLAWBACK_IS_RECIPIENT_WARNING);
+                    if !allow_clawback_to_recipient {
+                        wait_for_confirmation(
+                            "Press Enter to launch anyway (or use --allow-clawback-to-recipient to skip this check)...",
+                        )?;
+                    }
+                }
+            }
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let latest_timestamp = get_latest_timestamp(&cli).await?;
+            if streams
+                .iter()
+                .any(|stream| stream.end_timestamp < latest_timestamp)
+            {
+                eprintln!(
+                    "Warning: some streams end before the latest block time ({}), so their recipients will be able to claim everything immediately.",
+                    latest_timestamp
+                );
+                if !allow_past_end {
+                    wait_for_confirmation(
+                        "Press Enter to launch anyway (or use --allow-past-end to skip this check)...",
+                    )?;
                 }
             }
 
-            let Some(streaming_coin_id) = streaming_coin_id else {
-                return Err(CliError::UnknownStreamingCoinId);
-            };
+            let metadata = cat_metadata.get(Bytes32::new(asset_id));
+            let prefix = get_address_prefix(testnet11);
+            eprintln!(
+                "You're about to start {} streams of a CAT in one transaction",
+                streams.len()
+            );
+            eprintln!("Note: Sage RPC should be running on port 9257\n");
+            eprintln!("Asset ID: {}", hex::encode(asset_id));
+            for (index, stream) in streams.iter().enumerate() {
+                eprintln!(
+                    "#{}: {} to {} from {} to {} (clawback: {})",
+                    index + 1,
+                    metadata.format(stream.amount),
+                    stream.recipient,
+                    stream.start_timestamp,
+                    stream.end_timestamp,
+                    stream
+                        .clawback_ph
+                        .map(|clawback_ph| Address::new(clawback_ph, prefix.clone()).encode())
+                        .transpose()?
+                        .unwrap_or_else(|| "none".to_string())
+                );
+            }
+            eprintln!("Total: {}", metadata.format(sum));
+            eprintln!("Fee: {:.12}", fee as f64 / 1_000_000_000_000.0);
+            eprintln!("Mainnet?: {}", !testnet11);
+            wait_for_confirmation("Press Enter to continue...")?;
+
+            let mut launches = Vec::with_capacity(streams.len());
+            let mut payments = Vec::with_capacity(streams.len());
+            for stream in &streams {
+                let params = StreamParameters {
+                    recipient: stream.recipient_puzzle_hash,
+                    clawback_ph: stream.clawback_ph,
+                    start_time: stream.start_timestamp,
+                    end_time: stream.end_timestamp,
+                };
+                let inner_puzzle_hash = params.inner_puzzle_hash();
+                let streaming_cat_address =
+                    Address::new(inner_puzzle_hash.into(), prefix.clone()).encode()?;
+                let memos: Vec<String> = params
+                    .to_hints()
+                    .iter()
+                    .map(|b| hex::encode(b.to_vec()))
+                    .collect();
 
-            println!("Streaming coin id: 0x{}", streaming_coin_id);
+                payments.push(Payment {
+                    asset_id: Some(hex::encode(asset_id)),
+                    address: streaming_cat_address.clone(),
+                    amount: Amount::Number(stream.amount),
+                    memos: Some(memos.clone()),
+                });
+                launches.push((streaming_cat_address, inner_puzzle_hash, memos));
+            }
+            let inner_puzzle_hashes: Vec<Bytes32> = launches
+                .iter()
+                .map(|(_, inner_puzzle_hash, _)| (*inner_puzzle_hash).into())
+                .collect();
 
-            let streaming_coin_id = hex::decode(streaming_coin_id)
-                .map_err(|_| CliError::UnknownStreamingCoinId)?
-                .try_into()
-                .map_err(|_| CliError::UnknownStreamingCoinId)?;
-            println!(
-                "Stream id: {}",
-                Address::new(streaming_coin_id, get_stream_prefix(testnet11)).encode()?
-            );
+            check_sage_network(&client, &launches[0].0).await?;
+            eprintln!("Sending CAT...");
+            let response = client
+                .multi_send(MultiSend {
+                    payments,
+                    fee: Amount::Number(fee),
+                    auto_submit: true,
+                })
+                .await?;
+
+            let mut launch_results = Vec::with_capacity(streams.len());
+            let mut streaming_coin_ids = Vec::with_capacity(streams.len());
+            for (stream, (streaming_cat_address, inner_puzzle_hash, memos)) in
+                streams.iter().zip(launches)
+            {
+                let streaming_coin_id = find_streaming_coin_id(
+                    &response,
+                    asset_id,
+                    &streaming_cat_address,
+                    stream.amount,
+                )?;
+                streaming_coin_ids.push(streaming_coin_id);
+                launch_results.push(LaunchResult {
+                    stream_id: StreamId(streaming_coin_id).encode(&get_stream_prefix(testnet11))?,
+                    streaming_coin_id: format!("0x{}", hex::encode(streaming_coin_id.to_vec())),
+                    streaming_cat_address,
+                    inner_puzzle_hash: format!(
+                        "0x{}",
+                        hex::encode(Bytes32::from(inner_puzzle_hash).to_vec())
+                    ),
+                    memos,
+                });
+            }
 
-            println!("Waiting for mempool item to be confirmed...");
-            let cli = if testnet11 {
-                CoinsetClient::testnet11()
+            if json {
+                println!("{}", serde_json::to_string_pretty(&launch_results)?);
             } else {
-                CoinsetClient::mainnet()
-            };
+                for (stream, launch_result) in streams.iter().zip(&launch_results) {
+                    println!("{}: {}", stream.recipient, launch_result.stream_id);
+                }
+            }
+            if no_wait {
+                return Ok(());
+            }
+            if !json {
+                eprintln!("Waiting for mempool item to be confirmed...");
+            }
 
-            wait_for_coin(streaming_coin_id, &cli, false).await?;
-            println!("Confimed! :)");
+            wait_for_coins(&streaming_coin_ids, &cli).await?;
+            for ((stream, streaming_coin_id), inner_puzzle_hash) in streams
+                .iter()
+                .zip(&streaming_coin_ids)
+                .zip(inner_puzzle_hashes)
+            {
+                verify_launched_coin(
+                    &cli,
+                    *streaming_coin_id,
+                    Bytes32::new(asset_id),
+                    inner_puzzle_hash,
+                    stream.amount,
+                )
+                .await?;
+            }
+            if !json {
+                eprintln!(
+                    "Confimed! :) All {} streaming coins hold the launched amounts",
+                    streaming_coin_ids.len()
+                );
+            }
         }
         Commands::View {
             stream_id,
             testnet11,
+            from_file,
+            raw,
+            max_spends,
+            since,
+            block_time,
+            compact,
+            json,
         } => {
-            let cli = if testnet11 {
-                CoinsetClient::testnet11()
+            let (stream_id, testnet11) = if let Some(from_file) = from_file {
+                let export = StreamExport::read(&from_file)?;
+                let testnet11 = export.testnet11()?;
+                (export.stream_id, testnet11)
             } else {
-                CoinsetClient::mainnet()
+                let Some(stream_id) = stream_id else {
+                    unreachable!("clap requires the stream id unless --from-file is given");
+                };
+                (stream_id, testnet11)
             };
+            if block_time == 0 {
+                return Err(CliError::InvalidDuration(block_time.to_string()));
+            }
+            let since = since.as_deref().map(parse_timestamp).transpose()?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let mut timestamps = TimestampCache::default();
             let stream_prefix = get_stream_prefix(testnet11);
             let prefix = get_address_prefix(testnet11);
-            let _ = sync_stream(stream_id, &cli, stream_prefix, prefix, true, true).await?;
+            if json {
+                let timeline =
+                    sync_timeline(stream_id, &cli, &mut timestamps, &stream_prefix, &prefix)
+                        .await?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&StreamTimelineJson::new(
+                        &timeline,
+                        &stream_prefix,
+                        &prefix,
+                        &get_state_token_prefix(testnet11),
+                    )?)?
+                );
+            } else if compact {
+                let latest_stream = sync_stream(
+                    stream_id.clone(),
+                    &cli,
+                    &mut timestamps,
+                    stream_prefix,
+                    prefix,
+                    SyncOptions::default(),
+                )
+                .await?;
+                print_compact_summary(
+                    &stream_id,
+                    latest_stream.as_ref(),
+                    &cli,
+                    &mut timestamps,
+                    &cat_metadata,
+                )
+                .await?;
+            } else if let Some(latest_stream) = sync_stream(
+                stream_id,
+                &cli,
+                &mut timestamps,
+                stream_prefix,
+                prefix,
+                SyncOptions {
+                    print: true,
+                    print_claimable: true,
+                    raw,
+                    max_spends,
+                    cat_metadata: Some(&cat_metadata),
+                    since,
+                    block_time: Some(block_time),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .filter(|_| max_spends.is_none())
+            {
+                println!(
+                    "State token: {}",
+                    to_state_token(&latest_stream, &get_state_token_prefix(testnet11))?
+                );
+            }
         }
-        Commands::Claim {
+        Commands::Discover {
+            recipient,
+            include_finished,
+            start_height,
+            restart,
+            testnet11,
+        } => {
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let recipient_puzzle_hash = decode_address(&recipient, testnet11)?;
+
+            let streams = discover_streams(
+                &cli,
+                recipient_puzzle_hash,
+                &get_stream_prefix(testnet11),
+                include_finished,
+                start_height,
+                !restart,
+                None,
+            )
+            .await?;
+            print_discovered_streams(&streams);
+        }
+        Commands::StreamAddress {
+            recipient,
+            clawback_address,
+            start,
+            end,
+            asset_id,
+            testnet11,
+        } => {
+            let recipient_puzzle_hash = decode_address(&recipient, testnet11)?;
+            let clawback_ph = if clawback_address == "none" {
+                None
+            } else {
+                Some(decode_address(&clawback_address, testnet11)?)
+            };
+
+            let inner_puzzle_hash = StreamParameters {
+                recipient: recipient_puzzle_hash,
+                clawback_ph,
+                start_time: start,
+                end_time: end,
+            }
+            .inner_puzzle_hash();
+
+            // Same address `launch` sends the CAT to - wallets wrap it in the CAT layer
+            println!(
+                "Streaming CAT address: {}",
+                Address::new(inner_puzzle_hash.into(), get_address_prefix(testnet11)).encode()?
+            );
+            println!(
+                "Inner puzzle hash: 0x{}",
+                hex::encode(Bytes32::from(inner_puzzle_hash).to_vec())
+            );
+            if let Some(asset_id) = asset_id {
+                let asset_id = cat_metadata.resolve_asset_id(&asset_id)?;
+                let puzzle_hash: Bytes32 =
+                    CatArgs::curry_tree_hash(asset_id, inner_puzzle_hash).into();
+                println!("CAT puzzle hash: 0x{}", hex::encode(puzzle_hash.to_vec()));
+            }
+        }
+        Commands::Verify {
             stream_id,
-            fee,
+            recipient,
+            amount,
+            start,
+            end,
+            clawback_address,
+            asset_id,
             testnet11,
-            hardened,
-            max_derivations,
+            from_file,
         } => {
-            let cli = if testnet11 {
-                CoinsetClient::testnet11()
+            // Expected parameters - `None` for the optional ones means they aren't checked
+            // directly, only through the puzzle hash
+            let (
+                stream_id,
+                testnet11,
+                recipient_puzzle_hash,
+                amount,
+                start,
+                end,
+                clawback,
+                asset_id,
+            ) = if let Some(from_file) = from_file {
+                let export = StreamExport::read(&from_file)?;
+                let params = export.parameters()?;
+                (
+                    export.stream_id.clone(),
+                    export.testnet11()?,
+                    params.recipient,
+                    export.amount,
+                    params.start_time,
+                    params.end_time,
+                    Some(params.clawback_ph),
+                    Some(export.asset_id()?),
+                )
             } else {
-                CoinsetClient::mainnet()
+                let (Some(stream_id), Some(recipient), Some(amount), Some(start), Some(end)) =
+                    (stream_id, recipient, amount, start, end)
+                else {
+                    unreachable!("clap requires these arguments unless --from-file is given");
+                };
+                let asset_id = asset_id
+                    .map(|asset_id| cat_metadata.resolve_asset_id(&asset_id))
+                    .transpose()?;
+                (
+                    stream_id,
+                    testnet11,
+                    decode_address(&recipient, testnet11)?,
+                    parse_amount(amount, true)?,
+                    start,
+                    end,
+                    clawback_address
+                        .map(|clawback_address| {
+                            decode_address(&clawback_address, testnet11).map(Some)
+                        })
+                        .transpose()?,
+                    asset_id,
+                )
             };
 
-            println!("Fetching latest unspent coin...");
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let mut timestamps = TimestampCache::default();
+            let prefix = get_address_prefix(testnet11);
 
-            let latest_streamed_coin = sync_stream(
+            // Stopping right after the launch coin gives the stream as it was created
+            let launch_stream = sync_stream(
                 stream_id,
                 &cli,
+                &mut timestamps,
                 get_stream_prefix(testnet11),
-                get_address_prefix(testnet11),
-                true,
-                false,
+                prefix.clone(),
+                SyncOptions {
+                    max_spends: Some(0),
+                    ..Default::default()
+                },
             )
             .await?
-            .unwrap();
+            .ok_or(CliError::StreamUnavailable)?;
+            let info = &launch_stream.info;
+            let metadata = cat_metadata.get(launch_stream.asset_id);
 
-            let latest_timestamp = get_latest_timestamp(&cli).await?;
+            let mut checks = vec![
+                (
+                    "Recipient",
+                    Address::new(recipient_puzzle_hash, prefix.clone()).encode()?,
+                    Address::new(info.recipient, prefix.clone()).encode()?,
+                ),
+                (
+                    "Amount",
+                    metadata.format(amount),
+                    metadata.format(launch_stream.coin.amount),
+                ),
+                (
+                    "Start time",
+                    start.to_string(),
+                    info.last_payment_time.to_string(),
+                ),
+                ("End time", end.to_string(), info.end_time.to_string()),
+            ];
 
-            println!("Latest block timestamp: {}", latest_timestamp);
-            let claim_time = if latest_timestamp - 1 <= latest_streamed_coin.info.end_time {
-                latest_timestamp - 1
+            // Optional parameters default to the on-chain values, so only the puzzle hash
+            // check below covers them if they weren't given
+            let format_clawback = |clawback_ph: Option<Bytes32>| -> Result<String, CliError> {
+                Ok(match clawback_ph {
+                    Some(clawback_ph) => Address::new(clawback_ph, prefix.clone()).encode()?,
+                    None => "None".to_string(),
+                })
+            };
+            let clawback_ph = if let Some(clawback_ph) = clawback {
+                checks.push((
+                    "Clawback address",
+                    format_clawback(clawback_ph)?,
+                    format_clawback(info.clawback_ph)?,
+                ));
+                clawback_ph
             } else {
-                latest_streamed_coin.info.end_time
+                info.clawback_ph
             };
-            let claim_amount = latest_streamed_coin
-                .info
-                .amount_to_be_paid(latest_streamed_coin.coin.amount, claim_time);
+            let asset_id = if let Some(asset_id) = asset_id {
+                checks.push((
+                    "Asset id",
+                    hex::encode(asset_id.to_vec()),
+                    hex::encode(launch_stream.asset_id.to_vec()),
+                ));
+                asset_id
+            } else {
+                launch_stream.asset_id
+            };
+
+            // The launch coin's puzzle hash commits to all parameters at once
+            let expected_puzzle_hash: Bytes32 = CatArgs::curry_tree_hash(
+                asset_id,
+                StreamParameters {
+                    recipient: recipient_puzzle_hash,
+                    clawback_ph,
+                    start_time: start,
+                    end_time: end,
+                }
+                .inner_puzzle_hash(),
+            )
+            .into();
+            checks.push((
+                "Puzzle hash",
+                hex::encode(expected_puzzle_hash.to_vec()),
+                hex::encode(launch_stream.coin.puzzle_hash.to_vec()),
+            ));
 
-            println!("Claim amount: {:.3} CATs", claim_amount as f64 / 1000.0);
-            println!("Press 'Enter' to proceed");
-            let _ = std::io::stdin().read_line(&mut String::new());
+            let mut mismatches = 0;
+            for (name, expected, actual) in checks {
+                if expected == actual {
+                    println!("  {}: match ({})", name, actual);
+                } else {
+                    mismatches += 1;
+                    println!(
+                        "  {}: MISMATCH (expected {}, found {})",
+                        name, expected, actual
+                    );
+                }
+            }
 
-            let recipient = latest_streamed_coin.info.recipient;
-            let recipient_address =
-                Address::new(recipient, get_address_prefix(testnet11)).encode()?;
+            if info.clawback_ph == Some(info.recipient) {
+                eprintln!("{}", CLAWBACK_IS_RECIPIENT_WARNING);
+            }
+            if mismatches > 0 {
+                return Err(CliError::StreamMismatch(mismatches));
+            }
+            println!("Stream matches the expected parameters.");
+        }
+        Commands::Export {
+            stream_id,
+            out,
+            testnet11,
+        } => {
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let mut timestamps = TimestampCache::default();
+
+            let launch_stream = sync_stream(
+                stream_id.clone(),
+                &cli,
+                &mut timestamps,
+                get_stream_prefix(testnet11),
+                get_address_prefix(testnet11),
+                SyncOptions {
+                    max_spends: Some(0),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .ok_or(CliError::StreamUnavailable)?;
+
+            StreamExport::new(stream_id, &launch_stream, testnet11)?.write(&out)?;
+            eprintln!("Stream exported to {}", out);
+        }
+        #[cfg(feature = "server")]
+        Commands::Serve { bind, testnet11 } => {
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            server::serve(cli, testnet11, &bind).await?;
+        }
+        Commands::PuzzleInfo => {
+            let mut allocator = Allocator::new();
+            let puzzle = node_from_bytes(&mut allocator, &STREAM_PUZZLE)
+                .map_err(|_| CliError::PuzzleHashMismatch)?;
+            let computed_hash = tree_hash(&allocator, puzzle);
+
+            println!("Stream puzzle reveal: 0x{}", hex::encode(STREAM_PUZZLE));
             println!(
-                "Searching for key associated with address: {}",
-                recipient_address
+                "Stream puzzle hash: 0x{}",
+                hex::encode(STREAM_PUZZLE_HASH.to_bytes())
             );
+            println!(
+                "Tree hash of the reveal: 0x{}",
+                hex::encode(computed_hash.to_bytes())
+            );
+            println!("Curried arguments:");
+            println!("  1st curry: RECIPIENT, CLAWBACK_PH, END_TIME");
+            println!("  2nd curry: SELF_HASH (hash of the 1st-curried puzzle), LAST_PAYMENT_TIME");
+            println!("  The result is wrapped in the CAT puzzle of the streamed asset.");
 
-            let sage_client = SageClient::new().map_err(|e| {
-                eprintln!("Failed to create Sage client: {}", e);
-                CliError::HomeDirectoryNotFound
-            })?;
-            let public_key =
-                get_public_key(&sage_client, &recipient_address, max_derivations, hardened).await?;
+            if computed_hash != STREAM_PUZZLE_HASH {
+                return Err(CliError::PuzzleHashMismatch);
+            }
+            println!("The puzzle hash matches the reveal.");
+        }
+        Commands::Simulate {
+            amount,
+            start_timestamp,
+            end_timestamp,
+            claim_times,
+            clawback_at,
+            asset_id,
+        } => {
+            let amount = parse_amount(amount, true)?;
+            let metadata = asset_id
+                .map(|asset_id| cat_metadata.resolve_asset_id(&asset_id))
+                .transpose()?
+                .map(|asset_id| cat_metadata.get(asset_id))
+                .unwrap_or_default();
+            check_claim_schedule(start_timestamp, end_timestamp, &claim_times, clawback_at)?;
 
-            println!("Building spend bundle...");
-            let coin_id = generate_spend_bundle(
-                &sage_client,
-                latest_streamed_coin,
-                public_key,
-                recipient,
-                &recipient_address,
-                fee,
-                claim_time,
-                false,
+            let info =
+                StreamingPuzzleInfo::new(Bytes32::default(), None, end_timestamp, start_timestamp);
+
+            println!("Total amount: {}", metadata.format(amount));
+            for spend in simulate_stream(&info, amount, &claim_times, clawback_at) {
+                if spend.clawback {
+                    println!(
+                        "  Clawback at {}: paid {} to recipient, returned {}.",
+                        spend.payment_time,
+                        metadata.format(spend.paid),
+                        metadata.format(spend.returned)
+                    );
+                } else {
+                    println!(
+                        "  Claim at {}: paid {}, remaining {}.",
+                        spend.payment_time,
+                        metadata.format(spend.paid),
+                        metadata.format(spend.remaining)
+                    );
+                }
+            }
+        }
+        Commands::NextCoin {
+            stream_id,
+            at,
+            testnet11,
+        } => {
+            let at = at.as_deref().map(parse_timestamp).transpose()?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let mut timestamps = TimestampCache::default();
+
+            let latest_streamed_coin = sync_stream(
+                stream_id,
+                &cli,
+                &mut timestamps,
+                get_stream_prefix(testnet11),
+                get_address_prefix(testnet11),
+                SyncOptions::default(),
             )
-            .await?;
+            .await?
+            .ok_or(CliError::StreamUnavailable)?;
+
+            let info = &latest_streamed_coin.info;
+            let claim_time = match at {
+                Some(at) => at,
+                None => max_claim_time(info, timestamps.get(&cli).await?),
+            };
+            check_claim_window(claim_time, info)?;
+
+            let metadata = cat_metadata.get(latest_streamed_coin.asset_id);
+            println!(
+                "Latest coin id: 0x{}",
+                hex::encode(latest_streamed_coin.coin.coin_id().to_vec())
+            );
+            println!("Claim time: {}", claim_time);
+            println!(
+                "Claim amount: {}",
+                metadata.format(amount_to_be_paid(
+                    info,
+                    latest_streamed_coin.coin.amount,
+                    claim_time
+                ))
+            );
+            match child_after_claim(&latest_streamed_coin, claim_time) {
+                Some((child, _)) => {
+                    println!("Next coin id: 0x{}", hex::encode(child.coin_id().to_vec()));
+                    println!("Next coin amount: {}", metadata.format(child.amount));
+                }
+                None => println!(
+                    "A claim at this time pays out the rest of the stream - no coin follows."
+                ),
+            }
+        }
+        Commands::EstimateSchedule {
+            stream_id,
+            interval,
+            fee,
+            testnet11,
+        } => {
+            let interval = parse_duration(&interval)?;
+            let fee = parse_amount(fee, false)?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let mut timestamps = TimestampCache::default();
+
+            let latest_streamed_coin = sync_stream(
+                stream_id,
+                &cli,
+                &mut timestamps,
+                get_stream_prefix(testnet11),
+                get_address_prefix(testnet11),
+                SyncOptions::default(),
+            )
+            .await?
+            .ok_or(CliError::StreamUnavailable)?;
+            let latest_timestamp = timestamps.get(&cli).await?;
 
-            println!("Waiting for transaction to be confirmed...");
-            wait_for_coin(coin_id, &cli, true).await?;
-            println!("Confirmed :)");
+            // Claims happen every `interval` from now; the last one, at or after the end time,
+            // pays out whatever is left
+            let info = &latest_streamed_coin.info;
+            let first_claim_time = latest_timestamp.max(info.last_payment_time) + interval;
+            let mut claim_times: Vec<u64> = (0..)
+                .map(|step| first_claim_time + step * interval)
+                .take_while(|claim_time| *claim_time < info.end_time)
+                .collect();
+            claim_times.push(
+                claim_times
+                    .last()
+                    .map_or(first_claim_time, |last| last + interval),
+            );
+
+            let metadata = cat_metadata.get(latest_streamed_coin.asset_id);
+            println!(
+                "Remaining amount: {}",
+                metadata.format(latest_streamed_coin.coin.amount)
+            );
+            println!("Claiming every {}:", format_duration(interval));
+            let spends =
+                simulate_stream(info, latest_streamed_coin.coin.amount, &claim_times, None);
+            for spend in &spends {
+                println!(
+                    "  Claim at {}: paid {}, remaining {}.",
+                    spend.payment_time,
+                    metadata.format(spend.paid),
+                    metadata.format(spend.remaining)
+                );
+            }
+            println!(
+                "{} claims, total fees: {:.12} XCH",
+                spends.len(),
+                (spends.len() as u64 * fee) as f64 / 1_000_000_000_000.0
+            );
         }
-        Commands::Clawback {
+        Commands::Claim {
             stream_id,
+            amount,
             fee,
+            fee_mojos,
             testnet11,
             hardened,
+            auto,
             max_derivations,
+            refresh_derivations,
+            allow_low_fee,
+            state_token,
+            message_spend,
+            signer_file,
+            no_wait,
+            verify_payout,
+            allow_dust,
+            wait_for_block,
+            recipient_pubkey,
         } => {
-            let cli = if testnet11 {
-                CoinsetClient::testnet11()
+            check_max_derivations(max_derivations)?;
+            let fee = parse_fee(fee, fee_mojos)?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let network = NetworkConstants::select(testnet11, genesis_challenge);
+            let mut timestamps = TimestampCache::default();
+
+            let latest_streamed_coin = if let Some(state_token) = state_token {
+                let token_stream =
+                    from_state_token(&state_token, &get_state_token_prefix(testnet11))?;
+                check_state_token_stream(
+                    &token_stream,
+                    stream_id.clone(),
+                    &cli,
+                    &mut timestamps,
+                    testnet11,
+                )
+                .await?;
+                token_stream
             } else {
-                CoinsetClient::mainnet()
+                eprintln!("Fetching latest unspent coin...");
+
+                sync_stream(
+                    stream_id.clone(),
+                    &cli,
+                    &mut timestamps,
+                    get_stream_prefix(testnet11),
+                    get_address_prefix(testnet11),
+                    SyncOptions {
+                        print: true,
+                        cat_metadata: Some(&cat_metadata),
+                        ..Default::default()
+                    },
+                )
+                .await?
+                .ok_or(CliError::StreamUnavailable)?
             };
+            if let Some(spent_block_index) =
+                get_spent_block_index(&cli, latest_streamed_coin.coin.coin_id()).await?
+            {
+                eprintln!(
+                    "The streaming coin was already spent at block {} - nothing to do. Run 'view {}' to see the latest state of the stream.",
+                    spent_block_index, stream_id
+                );
+                return Ok(());
+            }
 
-            println!("Fetching latest unspent coin...");
+            let recipient_public_key = recipient_pubkey
+                .map(|public_key| {
+                    recipient_key_from_hex(&public_key, latest_streamed_coin.info.recipient)
+                })
+                .transpose()?;
 
-            let latest_streamed_coin = sync_stream(
-                stream_id,
+            let latest_timestamp = if wait_for_block {
+                let block = get_latest_block(&cli, None).await?;
+                eprintln!(
+                    "Waiting for a transaction block after height {}...",
+                    block.0
+                );
+                let (height, timestamp) = wait_for_next_block(&cli, block).await?;
+                eprintln!("New transaction block at height {}", height);
+                timestamp
+            } else {
+                timestamps.get(&cli).await?
+            };
+
+            eprintln!("Latest block timestamp: {}", latest_timestamp);
+            let message_spend = message_spend
+                .map(|path| {
+                    read_message_spend(
+                        &path,
+                        &latest_streamed_coin,
+                        latest_streamed_coin.info.recipient,
+                    )
+                })
+                .transpose()?;
+            let claim_time = if let Some(message_spend) = &message_spend {
+                message_spend.payment_time
+            } else if let Some(amount) = amount {
+                let Some(claim_time) = payment_time_for_amount(
+                    &latest_streamed_coin.info,
+                    latest_streamed_coin.coin.amount,
+                    parse_amount(amount, true)?,
+                ) else {
+                    return Err(CliError::ClaimAmountTooLarge);
+                };
+
+                if claim_time > latest_timestamp - 1 {
+                    return Err(CliError::ClaimAmountNotVested(claim_time));
+                }
+
+                claim_time
+            } else {
+                let claim_time = max_claim_time(&latest_streamed_coin.info, latest_timestamp);
+                if !wait_for_block && claim_time < latest_streamed_coin.info.end_time {
+                    let projection = project_per_block(
+                        &latest_streamed_coin.info,
+                        latest_streamed_coin.coin.amount,
+                        latest_timestamp,
+                        AVERAGE_BLOCK_TIME,
+                    );
+                    eprintln!(
+                        "Note: the claim is computed against the latest block. Each new block adds ~{} to the claimable amount - use --wait-for-block to claim against the next one.",
+                        cat_metadata
+                            .get(latest_streamed_coin.asset_id)
+                            .format(projection.amount_per_block)
+                    );
+                }
+
+                claim_time
+            };
+            check_claim_time(claim_time, &latest_streamed_coin.info, latest_timestamp)?;
+            let claim_amount = amount_to_be_paid(
+                &latest_streamed_coin.info,
+                latest_streamed_coin.coin.amount,
+                claim_time,
+            );
+
+            eprintln!(
+                "Claim amount: {}",
+                cat_metadata
+                    .get(latest_streamed_coin.asset_id)
+                    .format(claim_amount)
+            );
+            match child_after_claim(&latest_streamed_coin, claim_time) {
+                Some((child, child_info)) => {
+                    eprintln!("After the claim, the stream continues with:");
+                    eprintln!("  Coin id: {}", hex::encode(child.coin_id().to_vec()));
+                    eprintln!(
+                        "  Remaining amount: {}",
+                        cat_metadata
+                            .get(latest_streamed_coin.asset_id)
+                            .format(child.amount)
+                    );
+                    eprintln!("  Last payment time: {}", child_info.last_payment_time);
+                    if is_frozen_until_end(&child_info, child.amount) {
+                        eprintln!(
+                            "Warning: the remaining amount is so small that nothing more can be claimed until the stream ends at {}.",
+                            child_info.end_time
+                        );
+                        if !allow_dust {
+                            return Err(CliError::DustRemaining);
+                        }
+                    }
+                }
+                None => eprintln!("This claim pays out the rest of the stream."),
+            }
+            wait_for_confirmation("Press 'Enter' to proceed")?;
+
+            let message_spend = match signer_file {
+                Some(signer_file) => {
+                    let secret_key = find_secret_key(
+                        &load_master_key(&signer_file)?,
+                        latest_streamed_coin.info.recipient,
+                        max_derivations,
+                        hardened,
+                        auto,
+                    )?;
+                    Some(
+                        sign_message_spend(
+                            &cli,
+                            &latest_streamed_coin,
+                            &secret_key,
+                            latest_streamed_coin.info.recipient,
+                            fee,
+                            claim_time,
+                            network,
+                        )
+                        .await?,
+                    )
+                }
+                None => message_spend,
+            };
+            let payout = payout_coin(&latest_streamed_coin, claim_time);
+            let metadata = cat_metadata.get(latest_streamed_coin.asset_id);
+            let coin_id = if let Some(message_spend) = message_spend {
+                push_message_spend(&cli, latest_streamed_coin, message_spend, false, network)
+                    .await?
+            } else {
+                let sage_client = connect_sage()?;
+                submit_claim(
+                    &sage_client,
+                    &cli,
+                    latest_streamed_coin,
+                    claim_time,
+                    fee,
+                    testnet11,
+                    hardened,
+                    auto,
+                    max_derivations,
+                    refresh_derivations,
+                    recipient_public_key,
+                    allow_low_fee,
+                    false,
+                    network,
+                )
+                .await?
+            };
+            wait_for_spend(&cli, coin_id, &stream_id, no_wait).await?;
+            if verify_payout {
+                check_payout(&cli, payout, &metadata).await?;
+            }
+        }
+        Commands::SyncClaim {
+            stream_id,
+            fee,
+            fee_mojos,
+            testnet11,
+            hardened,
+            auto,
+            max_derivations,
+            refresh_derivations,
+            allow_low_fee,
+            no_wait,
+            verify_payout,
+            min_claim_value,
+            min_interval,
+        } => {
+            check_max_derivations(max_derivations)?;
+            let fee = parse_fee(fee, fee_mojos)?;
+            let min_claim_value = min_claim_value
+                .map(|min_claim_value| parse_amount(min_claim_value, true))
+                .transpose()?;
+            let min_interval = min_interval
+                .map(|min_interval| parse_duration(&min_interval))
+                .transpose()?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let network = NetworkConstants::select(testnet11, genesis_challenge);
+            let mut timestamps = TimestampCache::default();
+
+            let Some(latest_streamed_coin) = sync_stream(
+                stream_id.clone(),
                 &cli,
+                &mut timestamps,
                 get_stream_prefix(testnet11),
                 get_address_prefix(testnet11),
-                true,
-                false,
+                SyncOptions::default(),
             )
             .await?
-            .unwrap();
+            else {
+                eprintln!("Stream has finished - nothing to do.");
+                return Ok(());
+            };
 
-            let latest_timestamp = get_latest_timestamp(&cli).await?;
+            let latest_timestamp = timestamps.get(&cli).await?;
+            let claim_time = max_claim_time(&latest_streamed_coin.info, latest_timestamp);
+            check_claim_time(claim_time, &latest_streamed_coin.info, latest_timestamp)?;
+            let claim_amount = amount_to_be_paid(
+                &latest_streamed_coin.info,
+                latest_streamed_coin.coin.amount,
+                claim_time,
+            );
+            if claim_amount == 0 {
+                eprintln!("Nothing to claim yet - nothing to do.");
+                return Ok(());
+            }
+            if let Some(next_claim_time) = next_worthwhile_claim_time(
+                &latest_streamed_coin,
+                claim_time,
+                claim_amount,
+                min_claim_value,
+                min_interval,
+            ) {
+                eprintln!(
+                    "Only {} can be claimed - nothing to do until {} (in {}).",
+                    cat_metadata
+                        .get(latest_streamed_coin.asset_id)
+                        .format(claim_amount),
+                    next_claim_time,
+                    format_duration(next_claim_time.saturating_sub(latest_timestamp))
+                );
+                return Ok(());
+            }
 
-            println!("Latest block timestamp: {}", latest_timestamp);
-            let claim_time = if latest_timestamp + 600 <= latest_streamed_coin.info.end_time {
-                latest_timestamp + 600
+            eprintln!(
+                "Claiming {}...",
+                cat_metadata
+                    .get(latest_streamed_coin.asset_id)
+                    .format(claim_amount)
+            );
+            let payout = payout_coin(&latest_streamed_coin, claim_time);
+            let metadata = cat_metadata.get(latest_streamed_coin.asset_id);
+            let sage_client = connect_sage()?;
+            let coin_id = submit_claim(
+                &sage_client,
+                &cli,
+                latest_streamed_coin,
+                claim_time,
+                fee,
+                testnet11,
+                hardened,
+                auto,
+                max_derivations,
+                refresh_derivations,
+                None,
+                allow_low_fee,
+                true,
+                network,
+            )
+            .await?;
+            wait_for_spend(&cli, coin_id, &stream_id, no_wait).await?;
+            if verify_payout {
+                check_payout(&cli, payout, &metadata).await?;
+            }
+        }
+        Commands::Clawback {
+            stream_id,
+            fee,
+            fee_mojos,
+            testnet11,
+            hardened,
+            auto,
+            max_derivations,
+            refresh_derivations,
+            allow_low_fee,
+            state_token,
+            message_spend,
+            signer_file,
+            payment_time,
+            fair,
+            no_wait,
+        } => {
+            check_max_derivations(max_derivations)?;
+            let fee = parse_fee(fee, fee_mojos)?;
+            let cli = coinset_client(testnet11, coinset_url.as_deref());
+            let network = NetworkConstants::select(testnet11, genesis_challenge);
+            let mut timestamps = TimestampCache::default();
+
+            let latest_streamed_coin = if let Some(state_token) = state_token {
+                let token_stream =
+                    from_state_token(&state_token, &get_state_token_prefix(testnet11))?;
+                check_state_token_stream(
+                    &token_stream,
+                    stream_id.clone(),
+                    &cli,
+                    &mut timestamps,
+                    testnet11,
+                )
+                .await?;
+                token_stream
             } else {
-                latest_streamed_coin.info.end_time
+                eprintln!("Fetching latest unspent coin...");
+
+                sync_stream(
+                    stream_id.clone(),
+                    &cli,
+                    &mut timestamps,
+                    get_stream_prefix(testnet11),
+                    get_address_prefix(testnet11),
+                    SyncOptions {
+                        print: true,
+                        cat_metadata: Some(&cat_metadata),
+                        ..Default::default()
+                    },
+                )
+                .await?
+                .ok_or(CliError::StreamUnavailable)?
             };
-            let claim_amount = latest_streamed_coin
-                .info
-                .amount_to_be_paid(latest_streamed_coin.coin.amount, claim_time);
+            if let Some(spent_block_index) =
+                get_spent_block_index(&cli, latest_streamed_coin.coin.coin_id()).await?
+            {
+                eprintln!(
+                    "The streaming coin was already spent at block {} - nothing to do. Run 'view {}' to see the latest state of the stream.",
+                    spent_block_index, stream_id
+                );
+                return Ok(());
+            }
 
-            println!(
-                "Approx. claim amount: {:.3} CATs; Approx. return amount: {:.3} CATs",
-                claim_amount as f64 / 1000.0,
-                (latest_streamed_coin.coin.amount - claim_amount) as f64 / 1000.0
-            );
-            println!("Press 'Enter' to proceed");
-            let _ = std::io::stdin().read_line(&mut String::new());
+            let latest_timestamp = timestamps.get(&cli).await?;
 
+            eprintln!("Latest block timestamp: {}", latest_timestamp);
+            // Unlike claims, clawbacks assert `ASSERT_BEFORE_SECONDS_ABSOLUTE payment_time`, so
+            // the payment time has to be in the *future*: the spend is valid until a block with
+            // that timestamp is made. The +600 gives the transaction ~10 minutes to confirm; the
+            // recipient is paid exactly what has vested up to that time, which is what's shown.
+            if latest_streamed_coin.info.end_time <= latest_timestamp {
+                return Err(CliError::StreamFullyVested);
+            }
             let Some(clawback_ph) = latest_streamed_coin.info.clawback_ph else {
-                eprintln!("Stream cannot be clawed back :(");
-                return Err(CliError::InvalidStreamId());
+                return Err(CliError::NoClawbackAddress);
+            };
+            let message_spend = message_spend
+                .map(|path| read_message_spend(&path, &latest_streamed_coin, clawback_ph))
+                .transpose()?;
+            let payment_time = if let Some(message_spend) = &message_spend {
+                message_spend.payment_time
+            } else if let Some(payment_time) = payment_time {
+                payment_time
+            } else if fair {
+                // The earliest valid payment time - but it's only valid until the next
+                // transaction block, so the clawback has to make it into that block
+                eprintln!("Warning: with --fair, the clawback is only valid if it's included in the next transaction block. If it doesn't confirm, run the command again.");
+                latest_timestamp + 1
+            } else {
+                (latest_timestamp + 600).min(latest_streamed_coin.info.end_time)
+            };
+            if payment_time <= latest_timestamp {
+                return Err(CliError::PaymentTimePassed(payment_time, latest_timestamp));
+            }
+            // The preview clamps the payment time to the window, but the puzzle doesn't - a
+            // payment time outside of it would make the shown split wrong and the spend invalid
+            let info = &latest_streamed_coin.info;
+            if payment_time < info.last_payment_time || payment_time > info.end_time {
+                return Err(CliError::PaymentTimeOutsideWindow(
+                    payment_time,
+                    info.last_payment_time,
+                    info.end_time,
+                ));
+            }
+            let claim_amount = amount_to_be_paid(
+                &latest_streamed_coin.info,
+                latest_streamed_coin.coin.amount,
+                payment_time,
+            );
+            let return_amount = clawback_return_amount(
+                &latest_streamed_coin.info,
+                latest_streamed_coin.coin.amount,
+                payment_time,
+            );
+
+            let metadata = cat_metadata.get(latest_streamed_coin.asset_id);
+            eprintln!(
+                "Payment time: {} (the clawback must confirm before then - in {})",
+                payment_time,
+                format_duration(payment_time - latest_timestamp)
+            );
+            eprintln!(
+                "Amount paid to recipient: {}; Return amount: {}",
+                metadata.format(claim_amount),
+                metadata.format(return_amount)
+            );
+            wait_for_confirmation("Press 'Enter' to proceed")?;
+
+            let message_spend = match signer_file {
+                Some(signer_file) => {
+                    let secret_key = find_secret_key(
+                        &load_master_key(&signer_file)?,
+                        clawback_ph,
+                        max_derivations,
+                        hardened,
+                        auto,
+                    )?;
+                    Some(
+                        sign_message_spend(
+                            &cli,
+                            &latest_streamed_coin,
+                            &secret_key,
+                            clawback_ph,
+                            fee,
+                            payment_time,
+                            network,
+                        )
+                        .await?,
+                    )
+                }
+                None => message_spend,
             };
+            if let Some(message_spend) = message_spend {
+                let coin_id =
+                    push_message_spend(&cli, latest_streamed_coin, message_spend, true, network)
+                        .await?;
+
+                return wait_for_spend(&cli, coin_id, &stream_id, no_wait).await;
+            }
+
             let clawback_address =
                 Address::new(clawback_ph, get_address_prefix(testnet11)).encode()?;
-            println!(
+            eprintln!(
                 "Searching for key associated with address: {}",
                 clawback_address
             );
 
-            let sage_client = SageClient::new().map_err(|e| {
-                eprintln!("Failed to create Sage client: {}", e);
-                CliError::HomeDirectoryNotFound
-            })?;
-            let public_key =
-                get_public_key(&sage_client, &clawback_address, max_derivations, hardened).await?;
+            let sage_client = connect_sage()?;
+            let public_key = get_public_key(
+                &sage_client,
+                &clawback_address,
+                max_derivations,
+                hardened,
+                auto,
+                refresh_derivations,
+            )
+            .await?;
 
-            println!("Building spend bundle...");
+            eprintln!("Building spend bundle...");
             let coin_id = generate_spend_bundle(
                 &sage_client,
+                &cli,
                 latest_streamed_coin.clone(),
                 public_key,
                 clawback_ph,
                 &clawback_address,
-                fee.clone(),
-                claim_time,
+                fee,
+                payment_time,
                 true,
+                allow_low_fee,
+                false,
+                network,
             )
             .await?;
 
-            println!("Waiting for transaction to be confirmed...");
-            wait_for_coin(coin_id, &cli, true).await?;
-            println!("Confirmed :)");
+            wait_for_spend(&cli, coin_id, &stream_id, no_wait).await?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn coin_record(coin: Coin, spent_block_index: Option<u32>) -> CoinRecord {
+        CoinRecord {
+            coin,
+            coinbase: false,
+            confirmed_block_index: 100,
+            spent: spent_block_index.is_some(),
+            spent_block_index: spent_block_index.unwrap_or_default(),
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_retries_empty_responses() {
+        let calls = Cell::new(0);
+        let result = retry_with_backoff(4, tokio::time::Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move { Ok((call >= 2).then_some(call)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, Some(2));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_gives_up_after_all_attempts() {
+        let calls = Cell::new(0);
+        let result = retry_with_backoff(4, tokio::time::Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            async { Ok(None::<u32>) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, None);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_stops_on_errors() {
+        let calls = Cell::new(0);
+        let result = retry_with_backoff(4, tokio::time::Duration::ZERO, || {
+            calls.set(calls.get() + 1);
+            async { Err::<Option<u32>, _>(CliError::BlockRecordUnavailable) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(CliError::BlockRecordUnavailable)));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn check_max_derivations_rejects_zero() {
+        assert!(matches!(
+            check_max_derivations(0),
+            Err(CliError::InvalidMaxDerivations)
+        ));
+        assert!(check_max_derivations(1).is_ok());
+    }
+
+    #[test]
+    fn spent_block_index_of_response() {
+        let coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 1_000);
+        let response = |coin_record| GetCoinRecordResponse {
+            coin_record,
+            error: None,
+            success: true,
+        };
+
+        assert_eq!(
+            spent_block_index(response(Some(coin_record(coin, Some(120))))).unwrap(),
+            Some(120)
+        );
+        assert_eq!(
+            spent_block_index(response(Some(coin_record(coin, None)))).unwrap(),
+            None
+        );
+        assert_eq!(spent_block_index(response(None)).unwrap(), None);
+        assert!(matches!(
+            spent_block_index(GetCoinRecordResponse {
+                coin_record: None,
+                error: Some("busy".to_string()),
+                success: false,
+            }),
+            Err(CliError::CoinsetUnsuccessful(error)) if error == "busy"
+        ));
+    }
+
+    #[test]
+    fn degenerate_coin_records() {
+        let coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 1_000);
+        assert!(!is_degenerate_coin_record(
+            &coin_record(coin, None),
+            coin.coin_id()
+        ));
+
+        // Zeroed out by coinset, even though the id was requested
+        let zeroed = Coin::new(Bytes32::default(), Bytes32::default(), 0);
+        assert!(is_degenerate_coin_record(
+            &coin_record(zeroed, None),
+            coin.coin_id()
+        ));
+        assert!(is_degenerate_coin_record(
+            &coin_record(zeroed, None),
+            zeroed.coin_id()
+        ));
+
+        // Incomplete - e.g., the amount is missing, so the coin no longer hashes to its id
+        let incomplete = Coin::new(coin.parent_coin_info, coin.puzzle_hash, 0);
+        assert!(is_degenerate_coin_record(
+            &coin_record(incomplete, Some(120)),
+            coin.coin_id()
+        ));
+    }
+
+    #[test]
+    fn launch_walk_start_of_unspent_launch_coin() {
+        let launch_coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 1_000);
+
+        // Only the launch coin exists, so the walk stops right after parsing it
+        assert_eq!(
+            launch_walk_start(&coin_record(launch_coin, None)),
+            (launch_coin.parent_coin_info, true)
+        );
+        assert_eq!(
+            launch_walk_start(&coin_record(launch_coin, Some(120))),
+            (launch_coin.parent_coin_info, false)
+        );
+    }
+
+    fn nil_spend(coin: Coin) -> CoinSpend {
+        CoinSpend::new(
+            coin,
+            chia_protocol::Program::from_bytes(&[0x80]).unwrap(),
+            chia_protocol::Program::from_bytes(&[0x80]).unwrap(),
+        )
+    }
+
+    #[test]
+    fn order_spends_keeps_every_spend_once_in_dependency_order() {
+        let fee_coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 1_000);
+        let other_fee_coin = Coin::new(Bytes32::new([3; 32]), Bytes32::new([2; 32]), 500);
+        let lead_coin = Coin::new(fee_coin.coin_id(), Bytes32::new([4; 32]), 0);
+        let streamed_coin = Coin::new(Bytes32::new([5; 32]), Bytes32::new([6; 32]), 100);
+        let spends = vec![
+            nil_spend(streamed_coin),
+            nil_spend(lead_coin),
+            nil_spend(fee_coin),
+            nil_spend(other_fee_coin),
+        ];
+
+        let ordered =
+            order_spends(spends.clone(), lead_coin.coin_id(), streamed_coin.coin_id()).unwrap();
+        assert_eq!(ordered.len(), spends.len());
+        for spend in &spends {
+            assert_eq!(
+                ordered.iter().filter(|ordered| *ordered == spend).count(),
+                1
+            );
+        }
+
+        // The lead coin is created by a fee spend, and the streaming spend receives the lead
+        // coin's message - nothing in the bundle depends on the streaming spend, so it's last
+        let position = |coin: Coin| ordered.iter().position(|spend| spend.coin == coin).unwrap();
+        assert!(position(fee_coin) < position(lead_coin));
+        assert!(position(other_fee_coin) < position(lead_coin));
+        assert_eq!(position(lead_coin), 2);
+        assert_eq!(position(streamed_coin), 3);
+    }
+
+    #[test]
+    fn order_spends_rejects_duplicate_and_missing_spends() {
+        let lead_coin = Coin::new(Bytes32::new([1; 32]), Bytes32::new([2; 32]), 0);
+        let streamed_coin = Coin::new(Bytes32::new([3; 32]), Bytes32::new([4; 32]), 100);
+
+        for spends in [
+            vec![
+                nil_spend(lead_coin),
+                nil_spend(streamed_coin),
+                nil_spend(lead_coin),
+            ],
+            vec![nil_spend(lead_coin)],
+            vec![nil_spend(streamed_coin)],
+        ] {
+            assert!(matches!(
+                order_spends(spends, lead_coin.coin_id(), streamed_coin.coin_id()),
+                Err(CliError::InvalidSpendBundle(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn check_claim_time_bounds() {
+        let info = StreamingPuzzleInfo::new(Bytes32::new([1; 32]), None, 2_000, 1_000);
+
+        assert!(check_claim_time(1_000, &info, 1_001).is_ok());
+        assert!(check_claim_time(1_500, &info, 3_000).is_ok());
+        assert!(check_claim_time(2_000, &info, 3_000).is_ok());
+
+        assert!(matches!(
+            check_claim_time(999, &info, 3_000),
+            Err(CliError::ClaimTimeOutsideWindow(999, 1_000, 2_000))
+        ));
+        assert!(matches!(
+            check_claim_time(2_001, &info, 3_000),
+            Err(CliError::ClaimTimeOutsideWindow(2_001, 1_000, 2_000))
+        ));
+
+        // Not strictly below the latest block, so the spend couldn't be included yet
+        assert!(matches!(
+            check_claim_time(1_500, &info, 1_500),
+            Err(CliError::ClaimTimeInFuture(1_500, 1_500))
+        ));
+        assert!(matches!(
+            check_claim_time(1_600, &info, 1_500),
+            Err(CliError::ClaimTimeInFuture(1_600, 1_500))
+        ));
+    }
+
+    #[test]
+    fn public_key_not_found_hint_depends_on_mode() {
+        let auto = CliError::PublicKeyNotFound(5_000, true).to_string();
+        assert!(auto.contains("5000"));
+        assert!(auto.contains("--max-derivations"));
+        assert!(!auto.contains("--hardened"));
+
+        let single = CliError::PublicKeyNotFound(5_000, false).to_string();
+        assert!(single.contains("--max-derivations"));
+        assert!(single.contains("--hardened"));
+    }
+}
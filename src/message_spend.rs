@@ -0,0 +1,153 @@
+use chia::{bls::Signature, traits::Streamable};
+use chia_protocol::{Bytes32, Coin, CoinSpend, Program};
+use chia_wallet_sdk::{
+    coinset::{ChiaRpcClient, CoinsetClient},
+    driver::{SpendContext, StreamedCat},
+    types::Condition,
+};
+use clvmr::{run_program, ChiaDialect};
+use sage_api::{CoinSpendJson, SpendBundleJson};
+
+use crate::{
+    bundle::{assemble_and_verify, NetworkConstants, MAX_COST},
+    client::amount_to_u64,
+    CliError,
+};
+
+// Wallets that don't use the standard p2 puzzle (multisig, vaults, ...) can't be driven by
+// Sage here, so they can instead hand over a signed spend bundle that sends the authorization
+// message (SEND_MESSAGE mode 23, message = payment time, receiver = streaming coin id) from a
+// coin locked by the recipient (or clawback) puzzle hash. The payment time is read back from
+// that message, so it always matches what was signed.
+
+pub struct MessageSpend {
+    pub coin_spends: Vec<CoinSpend>,
+    pub aggregated_signature: Signature,
+    pub payment_time: u64,
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, CliError> {
+    Ok(hex::decode(hex.strip_prefix("0x").unwrap_or(hex))?)
+}
+
+fn decode_bytes32(hex: &str) -> Result<Bytes32, CliError> {
+    let bytes: [u8; 32] = decode_hex(hex)?
+        .try_into()
+        .map_err(|_| CliError::InvalidMessageSpend("expected 32 bytes".to_string()))?;
+    Ok(Bytes32::new(bytes))
+}
+
+pub fn coin_spend_from_json(spend: &CoinSpendJson) -> Result<CoinSpend, CliError> {
+    let coin = Coin::new(
+        decode_bytes32(&spend.coin.parent_coin_info)?,
+        decode_bytes32(&spend.coin.puzzle_hash)?,
+        amount_to_u64(&spend.coin.amount)?,
+    );
+
+    Ok(CoinSpend {
+        coin,
+        puzzle_reveal: Program::from_bytes(&decode_hex(&spend.puzzle_reveal)?)
+            .map_err(|_| CliError::InvalidMessageSpend("invalid puzzle reveal".to_string()))?,
+        solution: Program::from_bytes(&decode_hex(&spend.solution)?)
+            .map_err(|_| CliError::InvalidMessageSpend("invalid solution".to_string()))?,
+    })
+}
+
+/// Decodes a spend bundle in Sage's JSON format.
+pub fn spend_bundle_from_json(
+    bundle: &SpendBundleJson,
+) -> Result<(Vec<CoinSpend>, Signature), CliError> {
+    let coin_spends = bundle
+        .coin_spends
+        .iter()
+        .map(coin_spend_from_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    let signature: [u8; 96] = decode_hex(&bundle.aggregated_signature)?
+        .try_into()
+        .map_err(|_| CliError::InvalidMessageSpend("invalid signature length".to_string()))?;
+    let aggregated_signature = Signature::from_bytes(&signature)
+        .map_err(|_| CliError::InvalidMessageSpend("invalid signature".to_string()))?;
+
+    Ok((coin_spends, aggregated_signature))
+}
+
+/// Reads a signed spend bundle (Sage JSON format) and finds the message authorizing a spend
+/// of `streamed_coin`, sent from a coin with `p2_puzzle_hash`.
+pub fn read_message_spend(
+    path: &str,
+    streamed_coin: &StreamedCat,
+    p2_puzzle_hash: Bytes32,
+) -> Result<MessageSpend, CliError> {
+    let bytes = std::fs::read(path).map_err(CliError::ReadFile)?;
+    let bundle: SpendBundleJson = serde_json::from_slice(&bytes)
+        .map_err(|err| CliError::InvalidMessageSpend(err.to_string()))?;
+    let (coin_spends, aggregated_signature) = spend_bundle_from_json(&bundle)?;
+
+    let streamed_coin_id = streamed_coin.coin.coin_id();
+    let mut ctx = SpendContext::new();
+    let mut payment_time = None;
+    for coin_spend in coin_spends
+        .iter()
+        .filter(|coin_spend| coin_spend.coin.puzzle_hash == p2_puzzle_hash)
+    {
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+        let solution = ctx.alloc(&coin_spend.solution)?;
+        let output = run_program(&mut ctx, &ChiaDialect::new(0), puzzle, solution, MAX_COST)
+            .map_err(|_| CliError::InvalidMessageSpend("failed to run spend".to_string()))?
+            .1;
+
+        for condition in ctx.extract::<Vec<Condition>>(output)? {
+            let Condition::SendMessage(message) = condition else {
+                continue;
+            };
+            if message.mode != 23 || message.data.len() != 1 {
+                continue;
+            }
+            if ctx.extract::<Bytes32>(message.data[0])? != streamed_coin_id {
+                continue;
+            }
+
+            let message_ptr = ctx.alloc(&message.message)?;
+            payment_time = Some(ctx.extract::<u64>(message_ptr)?);
+        }
+    }
+
+    let Some(payment_time) = payment_time else {
+        return Err(CliError::InvalidMessageSpend(
+            "no message for the streaming coin sent by the expected puzzle hash".to_string(),
+        ));
+    };
+
+    Ok(MessageSpend {
+        coin_spends,
+        aggregated_signature,
+        payment_time,
+    })
+}
+
+/// Combines the message spend with the streamed CAT spend, verifies the signature and pushes
+/// it via coinset.org. Returns the id of the streaming coin that was spent.
+pub async fn push_message_spend(
+    cli: &CoinsetClient,
+    streamed_coin: StreamedCat,
+    message_spend: MessageSpend,
+    clawback: bool,
+    network: NetworkConstants,
+) -> Result<Bytes32, CliError> {
+    let mut ctx = SpendContext::new();
+    for coin_spend in message_spend.coin_spends {
+        ctx.insert(coin_spend);
+    }
+    streamed_coin.spend(&mut ctx, message_spend.payment_time, clawback)?;
+
+    let spend_bundle =
+        assemble_and_verify(ctx.take(), message_spend.aggregated_signature, network)?;
+    let resp = cli.push_tx(spend_bundle).await.map_err(CliError::Reqwest)?;
+    if !resp.success {
+        return Err(CliError::CoinsetUnsuccessful(
+            resp.error.unwrap_or_default(),
+        ));
+    }
+
+    Ok(streamed_coin.coin.coin_id())
+}
@@ -0,0 +1,21 @@
+//! Everything needed to work with streamed CATs in one `use streaming::prelude::*;`. The
+//! puzzle and driver types come from chia-wallet-sdk and are re-exported here unchanged.
+
+pub use chia_wallet_sdk::driver::{
+    StreamLayer, StreamPuzzle1stCurryArgs, StreamPuzzle2ndCurryArgs, StreamPuzzleSolution,
+    StreamedCat, StreamingPuzzleInfo, STREAM_PUZZLE, STREAM_PUZZLE_HASH,
+};
+
+pub use crate::{
+    error::{Result, StreamingError},
+    solution::{extract_solution, parse_launch, parse_launch_diagnostic, RejectReason},
+    state_token::{from_state_token, to_state_token},
+    stream_id::StreamId,
+    stream_parameters::StreamParameters,
+    timeline::{StreamClaim, StreamStatus, StreamTimeline},
+    vesting::{
+        amount_to_be_paid, child_after_claim, clawback_return_amount, is_frozen_until_end,
+        payment_time_for_amount, payout_coin, project_per_block, simulate_stream,
+        validate_time_window, BlockProjection, SimulatedSpend, AVERAGE_BLOCK_TIME,
+    },
+};
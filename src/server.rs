@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chia_protocol::Bytes32;
+use chia_wallet_sdk::{coinset::CoinsetClient, driver::StreamingPuzzleInfo, utils::Address};
+use serde::{Deserialize, Serialize};
+use streaming::{
+    state_token::to_state_token,
+    vesting::{amount_to_be_paid, project_per_block, simulate_stream, AVERAGE_BLOCK_TIME},
+};
+
+use crate::{
+    check_claim_schedule, decode_address,
+    discover::{discover_streams, DiscoveredStream},
+    get_address_prefix, get_state_token_prefix, get_stream_prefix, sync_stream, CliError,
+    ErrorKind, SyncOptions, TimestampCache,
+};
+
+// Read-only HTTP API mirroring `view`, `simulate` and `discover`. All amounts are in mojos.
+
+struct ServerState {
+    cli: CoinsetClient,
+    testnet11: bool,
+}
+
+type SharedState = Arc<ServerState>;
+
+impl IntoResponse for CliError {
+    fn into_response(self) -> Response {
+        let status = match self.kind() {
+            ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+            ErrorKind::Network => StatusCode::BAD_GATEWAY,
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Internal | ErrorKind::Sage | ErrorKind::Aborted => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        (
+            status,
+            Json(serde_json::json!({ "error": self.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+pub async fn serve(cli: CoinsetClient, testnet11: bool, bind: &str) -> Result<(), CliError> {
+    let state = Arc::new(ServerState { cli, testnet11 });
+    let app = Router::new()
+        .route("/streams/{stream_id}", get(view))
+        .route("/discover/{recipient}", get(discover))
+        .route("/simulate", get(simulate))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .map_err(CliError::Server)?;
+    eprintln!("Listening on {}", bind);
+    axum::serve(listener, app).await.map_err(CliError::Server)
+}
+
+#[derive(Debug, Serialize)]
+struct StreamState {
+    stream_id: String,
+    latest_coin_id: String,
+    asset_id: String,
+    recipient: String,
+    clawback_address: Option<String>,
+    end_time: u64,
+    last_payment_time: u64,
+    remaining_amount: u64,
+    claimable_amount: u64,
+    per_block: PerBlock,
+    state_token: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PerBlock {
+    block_time: u64,
+    amount_per_block: u64,
+    blocks_remaining: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ViewParams {
+    block_time: Option<u64>,
+}
+
+async fn view(
+    State(state): State<SharedState>,
+    Path(stream_id): Path<String>,
+    Query(params): Query<ViewParams>,
+) -> Result<Json<StreamState>, CliError> {
+    let block_time = params.block_time.unwrap_or(AVERAGE_BLOCK_TIME);
+    if block_time == 0 {
+        return Err(CliError::InvalidDuration(block_time.to_string()));
+    }
+
+    let prefix = get_address_prefix(state.testnet11);
+    let mut timestamps = TimestampCache::default();
+    let stream = sync_stream(
+        stream_id.clone(),
+        &state.cli,
+        &mut timestamps,
+        get_stream_prefix(state.testnet11),
+        prefix.clone(),
+        SyncOptions::default(),
+    )
+    .await?
+    .ok_or(CliError::StreamUnavailable)?;
+
+    let latest_timestamp = timestamps.get(&state.cli).await?;
+    let projection = project_per_block(
+        &stream.info,
+        stream.coin.amount,
+        latest_timestamp,
+        block_time,
+    );
+    Ok(Json(StreamState {
+        stream_id,
+        latest_coin_id: hex::encode(stream.coin.coin_id().to_vec()),
+        asset_id: hex::encode(stream.asset_id.to_vec()),
+        recipient: Address::new(stream.info.recipient, prefix.clone()).encode()?,
+        clawback_address: stream
+            .info
+            .clawback_ph
+            .map(|clawback_ph| Address::new(clawback_ph, prefix.clone()).encode())
+            .transpose()?,
+        end_time: stream.info.end_time,
+        last_payment_time: stream.info.last_payment_time,
+        remaining_amount: stream.coin.amount,
+        claimable_amount: amount_to_be_paid(&stream.info, stream.coin.amount, latest_timestamp),
+        per_block: PerBlock {
+            block_time,
+            amount_per_block: projection.amount_per_block,
+            blocks_remaining: projection.blocks_remaining,
+        },
+        state_token: to_state_token(&stream, &get_state_token_prefix(state.testnet11))?,
+    }))
+}
+
+// Discovery scans every block from `start_height` to the peak, so requests have to say where
+// to start and can't scan more than this many blocks (about 6 months of blocks)
+const MAX_DISCOVER_BLOCKS: u32 = 1_000_000;
+
+#[derive(Debug, Deserialize)]
+struct DiscoverParams {
+    #[serde(default)]
+    include_finished: bool,
+    start_height: u32,
+}
+
+async fn discover(
+    State(state): State<SharedState>,
+    Path(recipient): Path<String>,
+    Query(params): Query<DiscoverParams>,
+) -> Result<Json<Vec<DiscoveredStream>>, CliError> {
+    let recipient_puzzle_hash = decode_address(&recipient, state.testnet11)?;
+
+    Ok(Json(
+        discover_streams(
+            &state.cli,
+            recipient_puzzle_hash,
+            &get_stream_prefix(state.testnet11),
+            params.include_finished,
+            params.start_height,
+            false,
+            Some(MAX_DISCOVER_BLOCKS),
+        )
+        .await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct SimulateParams {
+    amount: u64,
+    start: u64,
+    end: u64,
+    // Comma-separated list of claim times
+    #[serde(default)]
+    claim_times: String,
+    clawback_at: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct SimulatedSpendJson {
+    payment_time: u64,
+    clawback: bool,
+    paid: u64,
+    returned: u64,
+    remaining: u64,
+}
+
+async fn simulate(
+    Query(params): Query<SimulateParams>,
+) -> Result<Json<Vec<SimulatedSpendJson>>, CliError> {
+    let claim_times = params
+        .claim_times
+        .split(',')
+        .filter(|time| !time.is_empty())
+        .map(|time| time.trim().parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| CliError::InvalidClaimSchedule)?;
+    check_claim_schedule(params.start, params.end, &claim_times, params.clawback_at)?;
+
+    let info = StreamingPuzzleInfo::new(Bytes32::default(), None, params.end, params.start);
+    Ok(Json(
+        simulate_stream(&info, params.amount, &claim_times, params.clawback_at)
+            .into_iter()
+            .map(|spend| SimulatedSpendJson {
+                payment_time: spend.payment_time,
+                clawback: spend.clawback,
+                paid: spend.paid,
+                returned: spend.returned,
+                remaining: spend.remaining,
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,347 @@
+use chia::puzzles::{cat::CatSolution, LineageProof};
+use chia_protocol::{Bytes, Coin, CoinSpend};
+use chia_wallet_sdk::{
+    driver::{
+        CatLayer, Layer, Puzzle, SpendContext, StreamLayer, StreamPuzzleSolution, StreamedCat,
+    },
+    types::{Condition, Memos},
+};
+use clvmr::NodePtr;
+use thiserror::Error;
+
+use crate::{error::Result, stream_parameters::StreamParameters};
+
+/// Reads the stream solution (payment time, amount paid, clawback flag) out of a spend of a
+/// streaming coin, without reconstructing the child coin like `from_parent_spend` does.
+/// `spend` has to be a spend of a streamed CAT - other CAT spends parse as garbage or fail.
+pub fn extract_solution(ctx: &mut SpendContext, spend: &CoinSpend) -> Result<StreamPuzzleSolution> {
+    let solution = ctx.alloc(&spend.solution)?;
+    let cat_solution = ctx.extract::<CatSolution<NodePtr>>(solution)?;
+
+    Ok(StreamLayer::parse_solution(
+        ctx,
+        cat_solution.inner_puzzle_solution,
+    )?)
+}
+
+/// Why an output of a launch spend wasn't recognized as a stream.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    #[error("the spent coin is not a CAT")]
+    NotCat,
+    #[error("no memos")]
+    NoMemos,
+    #[error("memos are not a list of atoms")]
+    MalformedMemos,
+    #[error("{0}")]
+    InvalidMemos(String),
+    #[error("memos decode, but don't reproduce the coin's puzzle hash")]
+    PuzzleHashMismatch,
+}
+
+/// Parses the stream launched by a spend of `parent_coin` (a regular CAT). Unlike
+/// `StreamedCat::from_parent_spend`, the launch memos don't need to have an exact length - extra
+/// memos after the stream ones are ignored. Returns `None` if the spend didn't launch a stream.
+pub fn parse_launch(
+    ctx: &mut SpendContext,
+    parent_coin: Coin,
+    parent_puzzle: Puzzle,
+    parent_solution: NodePtr,
+) -> Result<Option<StreamedCat>> {
+    Ok(parse_launch_diagnostic(ctx, parent_coin, parent_puzzle, parent_solution)?.0)
+}
+
+/// Same as `parse_launch`, but also returns why each CREATE_COIN before the stream (or all of
+/// them, if there's no stream) was rejected, as `(output index, reason)` pairs. A parent that
+/// isn't a CAT is reported with output index 0.
+pub fn parse_launch_diagnostic(
+    ctx: &mut SpendContext,
+    parent_coin: Coin,
+    parent_puzzle: Puzzle,
+    parent_solution: NodePtr,
+) -> Result<(Option<StreamedCat>, Vec<(usize, RejectReason)>)> {
+    let Some(cat_layer) = CatLayer::<Puzzle>::parse_puzzle(ctx, parent_puzzle)? else {
+        return Ok((None, vec![(0, RejectReason::NotCat)]));
+    };
+
+    let mut reasons = Vec::new();
+    let output = ctx.run(parent_puzzle.ptr(), parent_solution)?;
+    let create_coins = ctx
+        .extract::<Vec<Condition>>(output)?
+        .into_iter()
+        .filter_map(|condition| match condition {
+            Condition::CreateCoin(create_coin) => Some(create_coin),
+            _ => None,
+        });
+    for (index, create_coin) in create_coins.enumerate() {
+        let Memos::Some(memos) = create_coin.memos else {
+            reasons.push((index, RejectReason::NoMemos));
+            continue;
+        };
+        let Ok(memos) = ctx.extract::<Vec<Bytes>>(memos) else {
+            reasons.push((index, RejectReason::MalformedMemos));
+            continue;
+        };
+        let Some(params) = StreamParameters::from_launch_memos(
+            &memos,
+            cat_layer.asset_id,
+            create_coin.puzzle_hash,
+        ) else {
+            reasons.push((index, reject_reason(&memos)));
+            continue;
+        };
+
+        let stream = StreamedCat::new(
+            Coin::new(
+                parent_coin.coin_id(),
+                create_coin.puzzle_hash,
+                create_coin.amount,
+            ),
+            cat_layer.asset_id,
+            LineageProof {
+                parent_parent_coin_info: parent_coin.parent_coin_info,
+                parent_inner_puzzle_hash: cat_layer.inner_puzzle.curried_puzzle_hash().into(),
+                parent_amount: parent_coin.amount,
+            },
+            params.to_info(),
+        );
+        return Ok((Some(stream), reasons));
+    }
+
+    Ok((None, reasons))
+}
+
+// Mirrors `StreamParameters::from_launch_memos`: if any prefix decodes, the puzzle hash is what
+// didn't match; otherwise the decoding error of the longest prefix is the most telling one
+fn reject_reason(memos: &[Bytes]) -> RejectReason {
+    let decoded: Vec<_> = [4, 3]
+        .into_iter()
+        .filter(|len| memos.len() >= *len)
+        .map(|len| StreamParameters::from_hints(&memos[..len]))
+        .collect();
+
+    if decoded.iter().any(|params| params.is_ok()) {
+        return RejectReason::PuzzleHashMismatch;
+    }
+    match decoded.into_iter().find_map(|params| params.err()) {
+        Some(err) => RejectReason::InvalidMemos(err.to_string()),
+        None => {
+            RejectReason::InvalidMemos(format!("expected at least 3 memos, found {}", memos.len()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chia::{
+        bls::SecretKey,
+        puzzles::{cat::CatArgs, standard::StandardArgs},
+    };
+    use chia_protocol::Bytes32;
+    use chia_wallet_sdk::{
+        driver::{Cat, CatSpend, StandardLayer},
+        types::Conditions,
+    };
+
+    use super::*;
+    use crate::vesting::amount_to_be_paid;
+
+    fn params() -> StreamParameters {
+        StreamParameters {
+            recipient: Bytes32::new([1; 32]),
+            clawback_ph: Some(Bytes32::new([2; 32])),
+            start_time: 1_700_000_000,
+            end_time: 1_700_100_000,
+        }
+    }
+
+    fn stream() -> StreamedCat {
+        let asset_id = Bytes32::new([3; 32]);
+        let params = params();
+
+        StreamedCat::new(
+            Coin::new(
+                Bytes32::new([4; 32]),
+                CatArgs::curry_tree_hash(asset_id, params.inner_puzzle_hash()).into(),
+                1_000_000,
+            ),
+            asset_id,
+            LineageProof {
+                parent_parent_coin_info: Bytes32::new([5; 32]),
+                parent_inner_puzzle_hash: Bytes32::new([6; 32]),
+                parent_amount: 1_000_000,
+            },
+            params.to_info(),
+        )
+    }
+
+    // Serialized spend of `stream` at `payment_time`, as it would be fetched from coinset.org
+    fn stream_spend(stream: &StreamedCat, payment_time: u64, clawback: bool) -> CoinSpend {
+        let mut ctx = SpendContext::new();
+        stream.spend(&mut ctx, payment_time, clawback).unwrap();
+
+        ctx.take()
+            .into_iter()
+            .find(|coin_spend| coin_spend.coin == stream.coin)
+            .unwrap()
+    }
+
+    #[test]
+    fn extract_claim_solution() {
+        let stream = stream();
+        let payment_time = 1_700_025_000;
+        let spend = stream_spend(&stream, payment_time, false);
+
+        let solution = extract_solution(&mut SpendContext::new(), &spend).unwrap();
+        assert_eq!(solution.payment_time, payment_time);
+        assert!(!solution.clawback);
+        assert_eq!(solution.to_pay, 250_000);
+        assert_eq!(
+            solution.to_pay,
+            amount_to_be_paid(&stream.info, stream.coin.amount, payment_time)
+        );
+    }
+
+    #[test]
+    fn extract_clawback_solution() {
+        let stream = stream();
+        let payment_time = 1_700_050_000;
+        let spend = stream_spend(&stream, payment_time, true);
+
+        let solution = extract_solution(&mut SpendContext::new(), &spend).unwrap();
+        assert_eq!(solution.payment_time, payment_time);
+        assert!(solution.clawback);
+        assert_eq!(solution.to_pay, 500_000);
+    }
+
+    // Spends a 1_000 mojo CAT of the stream's asset id with `conditions` and returns what
+    // `parse_launch` gets from the parent spend
+    fn launch_spend(ctx: &mut SpendContext, conditions: Conditions) -> (Coin, Puzzle, NodePtr) {
+        let asset_id = stream().asset_id;
+        let public_key = SecretKey::from_seed(&[7; 32]).public_key();
+        let p2 = StandardLayer::new(public_key);
+        let p2_puzzle_hash: Bytes32 = StandardArgs::curry_tree_hash(public_key).into();
+        let cat = Cat::new(
+            Coin::new(
+                Bytes32::new([8; 32]),
+                CatArgs::curry_tree_hash(asset_id, p2_puzzle_hash.into()).into(),
+                1_000,
+            ),
+            Some(LineageProof {
+                parent_parent_coin_info: Bytes32::new([9; 32]),
+                parent_inner_puzzle_hash: p2_puzzle_hash,
+                parent_amount: 1_000,
+            }),
+            asset_id,
+            p2_puzzle_hash,
+        );
+
+        let inner_spend = p2.delegated_inner_spend(ctx, conditions).unwrap();
+        Cat::spend_all(ctx, &[CatSpend::new(cat, inner_spend)]).unwrap();
+        let coin_spend = ctx.take().remove(0);
+
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal).unwrap();
+        let solution = ctx.alloc(&coin_spend.solution).unwrap();
+        (coin_spend.coin, Puzzle::parse(ctx, puzzle), solution)
+    }
+
+    fn parse_launch_with_memos(memos: Vec<Bytes>) -> Option<StreamedCat> {
+        let mut ctx = SpendContext::new();
+        let memos = ctx.alloc(&memos).unwrap();
+        let conditions = Conditions::new().create_coin(
+            params().inner_puzzle_hash().into(),
+            1_000,
+            Memos::Some(memos),
+        );
+
+        let (parent_coin, parent_puzzle, parent_solution) = launch_spend(&mut ctx, conditions);
+        parse_launch(&mut ctx, parent_coin, parent_puzzle, parent_solution).unwrap()
+    }
+
+    fn assert_launched(stream: Option<StreamedCat>) {
+        let stream = stream.unwrap();
+        let params = params();
+
+        assert_eq!(stream.coin.amount, 1_000);
+        assert_eq!(stream.asset_id, self::stream().asset_id);
+        assert_eq!(stream.info.recipient, params.recipient);
+        assert_eq!(stream.info.clawback_ph, params.clawback_ph);
+        assert_eq!(stream.info.last_payment_time, params.start_time);
+        assert_eq!(stream.info.end_time, params.end_time);
+    }
+
+    #[test]
+    fn parse_launch_exact_memos() {
+        assert_launched(parse_launch_with_memos(params().to_hints()));
+    }
+
+    #[test]
+    fn parse_launch_with_note() {
+        let mut memos = params().to_hints();
+        memos.push(Bytes::new(b"payroll 2026-10".to_vec()));
+
+        assert_launched(parse_launch_with_memos(memos));
+    }
+
+    #[test]
+    fn parse_launch_with_six_memos() {
+        let mut memos = params().to_hints();
+        memos.push(Bytes::new(b"first".to_vec()));
+        memos.push(Bytes::new(vec![0; 32]));
+        assert_eq!(memos.len(), 6);
+
+        assert_launched(parse_launch_with_memos(memos));
+    }
+
+    #[test]
+    fn parse_launch_reject_reasons() {
+        let mut ctx = SpendContext::new();
+        let inner_puzzle_hash: Bytes32 = params().inner_puzzle_hash().into();
+
+        let malformed = ctx
+            .alloc(&vec![vec![Bytes::new(vec![1]), Bytes::new(vec![2])]])
+            .unwrap();
+        let too_few = ctx.alloc(&params().to_hints()[..2].to_vec()).unwrap();
+        let other_params = StreamParameters {
+            end_time: params().end_time + 1,
+            ..params()
+        };
+        let mismatched = ctx.alloc(&other_params.to_hints()).unwrap();
+
+        let conditions = Conditions::new()
+            .create_coin(inner_puzzle_hash, 250, Memos::None)
+            .create_coin(inner_puzzle_hash, 250, Memos::Some(malformed))
+            .create_coin(inner_puzzle_hash, 250, Memos::Some(too_few))
+            .create_coin(inner_puzzle_hash, 250, Memos::Some(mismatched));
+        let (parent_coin, parent_puzzle, parent_solution) = launch_spend(&mut ctx, conditions);
+
+        let (stream, reasons) =
+            parse_launch_diagnostic(&mut ctx, parent_coin, parent_puzzle, parent_solution).unwrap();
+        assert!(stream.is_none());
+        assert_eq!(
+            reasons,
+            vec![
+                (0, RejectReason::NoMemos),
+                (1, RejectReason::MalformedMemos),
+                (
+                    2,
+                    RejectReason::InvalidMemos("expected at least 3 memos, found 2".to_string())
+                ),
+                (3, RejectReason::PuzzleHashMismatch),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_launch_not_cat() {
+        let mut ctx = SpendContext::new();
+        let p2 = StandardLayer::new(SecretKey::from_seed(&[7; 32]).public_key());
+        let puzzle = p2.construct_puzzle(&mut ctx).unwrap();
+        let puzzle = Puzzle::parse(&ctx, puzzle);
+
+        let (stream, reasons) =
+            parse_launch_diagnostic(&mut ctx, stream().coin, puzzle, NodePtr::NIL).unwrap();
+        assert!(stream.is_none());
+        assert_eq!(reasons, vec![(0, RejectReason::NotCat)]);
+    }
+}
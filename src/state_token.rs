@@ -0,0 +1,151 @@
+use std::io::Cursor;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use chia::{
+    puzzles::{cat::CatArgs, LineageProof},
+    traits::Streamable,
+};
+use chia_protocol::{Bytes32, Coin};
+use chia_wallet_sdk::driver::{StreamPuzzle2ndCurryArgs, StreamedCat, StreamingPuzzleInfo};
+
+use crate::error::{Result, StreamingError};
+
+// A state token captures the *current* streaming coin (unlike the stream id, which
+// only points to the launch coin), so claims can be built without re-syncing the
+// whole history. Layout (streamable encoding, then bech32m):
+//   coin | asset_id | parent_parent_coin_info | parent_inner_puzzle_hash | parent_amount
+//   | recipient | clawback_ph (optional) | end_time | last_payment_time
+
+pub fn to_state_token(stream: &StreamedCat, prefix: &str) -> Result<String> {
+    let mut bytes = Vec::new();
+
+    stream
+        .coin
+        .stream(&mut bytes)
+        .and_then(|_| stream.asset_id.stream(&mut bytes))
+        .and_then(|_| stream.proof.parent_parent_coin_info.stream(&mut bytes))
+        .and_then(|_| stream.proof.parent_inner_puzzle_hash.stream(&mut bytes))
+        .and_then(|_| stream.proof.parent_amount.stream(&mut bytes))
+        .and_then(|_| stream.info.recipient.stream(&mut bytes))
+        .and_then(|_| stream.info.clawback_ph.stream(&mut bytes))
+        .and_then(|_| stream.info.end_time.stream(&mut bytes))
+        .and_then(|_| stream.info.last_payment_time.stream(&mut bytes))
+        .map_err(|_| StreamingError::InvalidStateToken)?;
+
+    Ok(bech32::encode(prefix, bytes.to_base32(), Variant::Bech32m)?)
+}
+
+pub fn from_state_token(token: &str, prefix: &str) -> Result<StreamedCat> {
+    let (hrp, data, variant) =
+        bech32::decode(token).map_err(|_| StreamingError::InvalidStateToken)?;
+    if hrp != prefix || variant != Variant::Bech32m {
+        return Err(StreamingError::InvalidStateToken);
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data).map_err(|_| StreamingError::InvalidStateToken)?;
+    let mut cursor = Cursor::new(bytes.as_slice());
+
+    let stream = parse_stream(&mut cursor).map_err(|_| StreamingError::InvalidStateToken)?;
+    if cursor.position() as usize != bytes.len() {
+        return Err(StreamingError::InvalidStateToken);
+    }
+
+    // The token is only trusted if its puzzle info reproduces the coin's puzzle hash
+    if stream_puzzle_hash(&stream) != stream.coin.puzzle_hash {
+        return Err(StreamingError::StateTokenPuzzleHashMismatch);
+    }
+
+    Ok(stream)
+}
+
+fn stream_puzzle_hash(stream: &StreamedCat) -> Bytes32 {
+    let inner_puzzle_hash = StreamPuzzle2ndCurryArgs::curry_tree_hash(
+        stream.info.recipient,
+        stream.info.clawback_ph,
+        stream.info.end_time,
+        stream.info.last_payment_time,
+    );
+    CatArgs::curry_tree_hash(stream.asset_id, inner_puzzle_hash).into()
+}
+
+fn parse_stream(cursor: &mut Cursor<&[u8]>) -> chia::traits::Result<StreamedCat> {
+    let coin = Coin::parse::<false>(cursor)?;
+    let asset_id = Bytes32::parse::<false>(cursor)?;
+    let proof = LineageProof {
+        parent_parent_coin_info: Bytes32::parse::<false>(cursor)?,
+        parent_inner_puzzle_hash: Bytes32::parse::<false>(cursor)?,
+        parent_amount: u64::parse::<false>(cursor)?,
+    };
+    let info = StreamingPuzzleInfo::new(
+        Bytes32::parse::<false>(cursor)?,
+        Option::<Bytes32>::parse::<false>(cursor)?,
+        u64::parse::<false>(cursor)?,
+        u64::parse::<false>(cursor)?,
+    );
+
+    Ok(StreamedCat::new(coin, asset_id, proof, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(clawback_ph: Option<Bytes32>) -> StreamedCat {
+        let asset_id = Bytes32::new([1; 32]);
+        let info = StreamingPuzzleInfo::new(Bytes32::new([2; 32]), clawback_ph, 2_000, 1_000);
+        let mut stream = StreamedCat::new(
+            Coin::new(Bytes32::new([3; 32]), Bytes32::default(), 1_000_000),
+            asset_id,
+            LineageProof {
+                parent_parent_coin_info: Bytes32::new([4; 32]),
+                parent_inner_puzzle_hash: Bytes32::new([5; 32]),
+                parent_amount: 1_500_000,
+            },
+            info,
+        );
+        stream.coin.puzzle_hash = stream_puzzle_hash(&stream);
+        stream
+    }
+
+    #[test]
+    fn state_token_round_trip() {
+        for clawback_ph in [None, Some(Bytes32::new([6; 32]))] {
+            let stream = stream(clawback_ph);
+            let token = to_state_token(&stream, "streamstate").unwrap();
+            let decoded = from_state_token(&token, "streamstate").unwrap();
+
+            assert_eq!(decoded.coin, stream.coin);
+            assert_eq!(decoded.asset_id, stream.asset_id);
+            assert_eq!(decoded.proof, stream.proof);
+            assert_eq!(decoded.info.recipient, stream.info.recipient);
+            assert_eq!(decoded.info.clawback_ph, stream.info.clawback_ph);
+            assert_eq!(decoded.info.end_time, stream.info.end_time);
+            assert_eq!(
+                decoded.info.last_payment_time,
+                stream.info.last_payment_time
+            );
+        }
+    }
+
+    #[test]
+    fn state_token_with_wrong_prefix_is_rejected() {
+        let token = to_state_token(&stream(None), "tstreamstate").unwrap();
+
+        assert!(matches!(
+            from_state_token(&token, "streamstate"),
+            Err(StreamingError::InvalidStateToken)
+        ));
+    }
+
+    #[test]
+    fn state_token_with_mismatched_puzzle_hash_is_rejected() {
+        let mut stream = stream(None);
+        stream.info.end_time += 1;
+        let token = to_state_token(&stream, "streamstate").unwrap();
+
+        assert!(matches!(
+            from_state_token(&token, "streamstate"),
+            Err(StreamingError::StateTokenPuzzleHashMismatch)
+        ));
+    }
+}
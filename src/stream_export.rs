@@ -0,0 +1,98 @@
+use chia_protocol::Bytes32;
+use chia_wallet_sdk::{driver::StreamedCat, utils::Address};
+use serde::{Deserialize, Serialize};
+use streaming::stream_parameters::StreamParameters;
+
+use crate::{decode_address, get_address_prefix, CliError};
+
+// `export` writes everything needed to check (and later claim) a stream into one JSON file, so
+// it can be handed to the recipient. Addresses are used instead of puzzle hashes to keep the
+// file readable; the network determines their prefix.
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamExport {
+    pub stream_id: String,
+    /// "mainnet" or "testnet11"
+    pub network: String,
+    pub asset_id: String,
+    /// Launched amount, in mojos
+    pub amount: u64,
+    pub recipient: String,
+    pub clawback_address: Option<String>,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+impl StreamExport {
+    /// Export of a stream from its launch coin (as returned by `sync_stream` with
+    /// `max_spends: Some(0)`).
+    pub fn new(
+        stream_id: String,
+        launch_stream: &StreamedCat,
+        testnet11: bool,
+    ) -> Result<Self, CliError> {
+        let prefix = get_address_prefix(testnet11);
+        let info = &launch_stream.info;
+
+        Ok(Self {
+            stream_id,
+            network: if testnet11 { "testnet11" } else { "mainnet" }.to_string(),
+            asset_id: hex::encode(launch_stream.asset_id.to_vec()),
+            amount: launch_stream.coin.amount,
+            recipient: Address::new(info.recipient, prefix.clone()).encode()?,
+            clawback_address: info
+                .clawback_ph
+                .map(|clawback_ph| Address::new(clawback_ph, prefix.clone()).encode())
+                .transpose()?,
+            start_time: info.last_payment_time,
+            end_time: info.end_time,
+        })
+    }
+
+    pub fn read(path: &str) -> Result<Self, CliError> {
+        let contents = std::fs::read_to_string(path).map_err(CliError::ReadFile)?;
+        let export: Self = serde_json::from_str(&contents)
+            .map_err(|err| CliError::InvalidExportFile(err.to_string()))?;
+        export.testnet11()?;
+
+        Ok(export)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), CliError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?).map_err(CliError::WriteFile)
+    }
+
+    pub fn testnet11(&self) -> Result<bool, CliError> {
+        match self.network.as_str() {
+            "mainnet" => Ok(false),
+            "testnet11" => Ok(true),
+            network => Err(CliError::InvalidExportFile(format!(
+                "unknown network '{}'",
+                network
+            ))),
+        }
+    }
+
+    pub fn asset_id(&self) -> Result<Bytes32, CliError> {
+        let asset_id: [u8; 32] = hex::decode(&self.asset_id)
+            .map_err(|_| CliError::InvalidAssetId)?
+            .try_into()
+            .map_err(|_| CliError::InvalidAssetId)?;
+        Ok(Bytes32::new(asset_id))
+    }
+
+    pub fn parameters(&self) -> Result<StreamParameters, CliError> {
+        let testnet11 = self.testnet11()?;
+
+        Ok(StreamParameters {
+            recipient: decode_address(&self.recipient, testnet11)?,
+            clawback_ph: self
+                .clawback_address
+                .as_deref()
+                .map(|clawback_address| decode_address(clawback_address, testnet11))
+                .transpose()?,
+            start_time: self.start_time,
+            end_time: self.end_time,
+        })
+    }
+}
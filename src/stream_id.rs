@@ -0,0 +1,78 @@
+use bech32::{FromBase32, ToBase32, Variant};
+use chia_protocol::Bytes32;
+
+use crate::error::{Result, StreamingError};
+
+/// Bech32m-encoded id of a stream - the coin id of the streaming coin created at launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamId(pub Bytes32);
+
+impl StreamId {
+    pub fn decode(stream_id: &str, prefix: &str) -> Result<Self> {
+        let (hrp, data, variant) =
+            bech32::decode(stream_id).map_err(|_| StreamingError::InvalidStreamId)?;
+        if hrp != prefix || variant != Variant::Bech32m {
+            return Err(StreamingError::InvalidStreamId);
+        }
+
+        let bytes = Vec::<u8>::from_base32(&data).map_err(|_| StreamingError::InvalidStreamId)?;
+        let coin_id: [u8; 32] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| StreamingError::InvalidStreamIdLength(bytes.len()))?;
+
+        Ok(Self(Bytes32::new(coin_id)))
+    }
+
+    pub fn encode(&self, prefix: &str) -> Result<String> {
+        Ok(bech32::encode(
+            prefix,
+            self.0.to_vec().to_base32(),
+            Variant::Bech32m,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_bytes(bytes: &[u8], prefix: &str) -> String {
+        bech32::encode(prefix, bytes.to_base32(), Variant::Bech32m).unwrap()
+    }
+
+    #[test]
+    fn stream_id_round_trip() {
+        let stream_id = StreamId(Bytes32::new([7; 32]));
+        let encoded = stream_id.encode("stream").unwrap();
+
+        assert!(encoded.starts_with("stream1"));
+        assert_eq!(StreamId::decode(&encoded, "stream").unwrap(), stream_id);
+    }
+
+    #[test]
+    fn stream_id_wrong_length() {
+        for len in [0, 31, 33] {
+            let encoded = encode_bytes(&vec![7; len], "stream");
+
+            assert!(matches!(
+                StreamId::decode(&encoded, "stream"),
+                Err(StreamingError::InvalidStreamIdLength(actual)) if actual == len
+            ));
+        }
+    }
+
+    #[test]
+    fn stream_id_wrong_prefix_or_variant() {
+        let encoded = StreamId(Bytes32::new([7; 32])).encode("txch").unwrap();
+        assert!(matches!(
+            StreamId::decode(&encoded, "stream"),
+            Err(StreamingError::InvalidStreamId)
+        ));
+
+        let bech32 = bech32::encode("stream", [7; 32].to_base32(), Variant::Bech32).unwrap();
+        assert!(matches!(
+            StreamId::decode(&bech32, "stream"),
+            Err(StreamingError::InvalidStreamId)
+        ));
+    }
+}
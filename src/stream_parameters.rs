@@ -0,0 +1,157 @@
+use chia::puzzles::cat::CatArgs;
+use chia_protocol::{Bytes, Bytes32};
+use chia_wallet_sdk::driver::{StreamPuzzle2ndCurryArgs, StreamingPuzzleInfo};
+use clvm_utils::TreeHash;
+
+use crate::error::{Result, StreamingError};
+
+/// Parameters a stream is launched with. They fully determine the streaming puzzle and are
+/// encoded in the launch memos as: recipient hint, clawback puzzle hash (if any), start time,
+/// end time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamParameters {
+    pub recipient: Bytes32,
+    pub clawback_ph: Option<Bytes32>,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+impl StreamParameters {
+    /// Puzzle info of the launch coin - nothing has been paid yet, so the last payment time
+    /// is the start time.
+    pub fn to_info(&self) -> StreamingPuzzleInfo {
+        StreamingPuzzleInfo::new(
+            self.recipient,
+            self.clawback_ph,
+            self.end_time,
+            self.start_time,
+        )
+    }
+
+    /// Inner puzzle hash of the launch coin (the address the CAT is sent to).
+    pub fn inner_puzzle_hash(&self) -> TreeHash {
+        StreamPuzzle2ndCurryArgs::curry_tree_hash(
+            self.recipient,
+            self.clawback_ph,
+            self.end_time,
+            self.start_time,
+        )
+    }
+
+    pub fn to_hints(&self) -> Vec<Bytes> {
+        self.to_info().get_launch_hints()
+    }
+
+    pub fn from_hints(hints: &[Bytes]) -> Result<Self> {
+        let (recipient, clawback_ph, start_time, end_time) = match hints {
+            [recipient, start_time, end_time] => (recipient, None, start_time, end_time),
+            [recipient, clawback_ph, start_time, end_time] => {
+                (recipient, Some(clawback_ph), start_time, end_time)
+            }
+            _ => {
+                return Err(StreamingError::InvalidLaunchHints(format!(
+                    "expected 3 or 4 memos, found {}",
+                    hints.len()
+                )))
+            }
+        };
+
+        Ok(Self {
+            recipient: hint_to_bytes32(recipient)?,
+            clawback_ph: clawback_ph.map(hint_to_bytes32).transpose()?,
+            start_time: hint_to_u64(start_time)?,
+            end_time: hint_to_u64(end_time)?,
+        })
+    }
+
+    /// Finds the parameters in the memos of the CREATE_COIN that launched a streamed CAT of
+    /// `asset_id` with `puzzle_hash`. The stream memos come first and anything after them (e.g.,
+    /// a note) is ignored - since that makes the layout ambiguous, both the 3 and 4 memo
+    /// prefixes are tried and the one that reproduces the puzzle hash is returned.
+    pub fn from_launch_memos(
+        memos: &[Bytes],
+        asset_id: Bytes32,
+        puzzle_hash: Bytes32,
+    ) -> Option<Self> {
+        [4, 3]
+            .into_iter()
+            .filter(|len| memos.len() >= *len)
+            .filter_map(|len| Self::from_hints(&memos[..len]).ok())
+            .find(|params| {
+                CatArgs::curry_tree_hash(asset_id, params.inner_puzzle_hash()) == puzzle_hash.into()
+            })
+    }
+}
+
+fn hint_to_bytes32(hint: &Bytes) -> Result<Bytes32> {
+    let bytes: [u8; 32] = hint.to_vec().try_into().map_err(|_| {
+        StreamingError::InvalidLaunchHints(format!("expected 32 bytes, found {}", hint.len()))
+    })?;
+    Ok(Bytes32::new(bytes))
+}
+
+// Timestamps are encoded as (unsigned) CLVM integers
+fn hint_to_u64(hint: &Bytes) -> Result<u64> {
+    hint.iter()
+        .try_fold(0u64, |acc, byte| {
+            acc.checked_mul(256).map(|acc| acc + *byte as u64)
+        })
+        .ok_or_else(|| StreamingError::InvalidLaunchHints("timestamp too large".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_round_trip() {
+        let times = [
+            (1_700_000_000, 1_800_000_000),
+            (0, 127),
+            (128, 255),
+            (u32::MAX as u64, u64::MAX),
+        ];
+
+        for clawback_ph in [None, Some(Bytes32::new([2; 32]))] {
+            for (start_time, end_time) in times {
+                let params = StreamParameters {
+                    recipient: Bytes32::new([1; 32]),
+                    clawback_ph,
+                    start_time,
+                    end_time,
+                };
+
+                let hints = params.to_hints();
+                assert_eq!(hints.len(), if clawback_ph.is_some() { 4 } else { 3 });
+                assert_eq!(StreamParameters::from_hints(&hints).unwrap(), params);
+            }
+        }
+    }
+
+    #[test]
+    fn launch_memos_round_trip() {
+        let asset_id = Bytes32::new([3; 32]);
+
+        for clawback_ph in [None, Some(Bytes32::new([2; 32]))] {
+            let params = StreamParameters {
+                recipient: Bytes32::new([1; 32]),
+                clawback_ph,
+                start_time: 1_700_000_000,
+                end_time: 1_800_000_000,
+            };
+            let puzzle_hash = CatArgs::curry_tree_hash(asset_id, params.inner_puzzle_hash()).into();
+
+            let mut memos = params.to_hints();
+            assert_eq!(
+                StreamParameters::from_launch_memos(&memos, asset_id, puzzle_hash),
+                Some(params)
+            );
+
+            memos.push(Bytes::new(b"note".to_vec()));
+            assert_eq!(
+                StreamParameters::from_launch_memos(&memos, asset_id, puzzle_hash),
+                Some(params)
+            );
+        }
+    }
+}
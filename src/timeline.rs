@@ -0,0 +1,93 @@
+use chia_protocol::Bytes32;
+use chia_wallet_sdk::driver::StreamedCat;
+
+use crate::{stream_parameters::StreamParameters, vesting::amount_to_be_paid};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStatus {
+    /// The latest streaming coin is unspent
+    Active,
+    /// Everything was paid out to the recipient
+    Finished,
+    /// The clawback address took back what hadn't vested yet
+    ClawedBack,
+}
+
+/// A spend of a streaming coin that paid the recipient. Clawbacks also pay out what has vested,
+/// so they're included with `clawback` set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamClaim {
+    /// Id of the spent streaming coin
+    pub coin_id: Bytes32,
+    pub spent_block_index: u32,
+    /// Timestamp of the block the spend was included in
+    pub timestamp: u64,
+    pub amount: u64,
+    pub clawback: bool,
+}
+
+/// Everything known about a stream at a point in time - its parameters, past claims and
+/// current state - so UIs don't have to piece it together from the individual coins.
+#[derive(Debug, Clone)]
+pub struct StreamTimeline {
+    /// Id of the launch coin
+    pub stream_id: Bytes32,
+    pub asset_id: Bytes32,
+    /// Launched amount
+    pub amount: u64,
+    pub parameters: StreamParameters,
+    /// Oldest first
+    pub claims: Vec<StreamClaim>,
+    /// The unspent streaming coin, unless the stream has finished or was clawed back
+    pub latest: Option<StreamedCat>,
+    pub status: StreamStatus,
+    /// Amount that can be claimed as of `timestamp`
+    pub claimable_now: u64,
+    /// When everything will have vested - `None` for clawed back streams
+    pub fully_vested_at: Option<u64>,
+    /// Timestamp (usually of the latest block) the timeline was assembled at
+    pub timestamp: u64,
+}
+
+impl StreamTimeline {
+    /// Assembles the timeline of the stream launched as `launch` (the launch coin, before any
+    /// claims) from its `claims` and latest unspent coin.
+    pub fn new(
+        launch: &StreamedCat,
+        claims: Vec<StreamClaim>,
+        latest: Option<StreamedCat>,
+        timestamp: u64,
+    ) -> Self {
+        let status = if latest.is_some() {
+            StreamStatus::Active
+        } else if claims.last().is_some_and(|claim| claim.clawback) {
+            StreamStatus::ClawedBack
+        } else {
+            StreamStatus::Finished
+        };
+
+        Self {
+            stream_id: launch.coin.coin_id(),
+            asset_id: launch.asset_id,
+            amount: launch.coin.amount,
+            parameters: StreamParameters {
+                recipient: launch.info.recipient,
+                clawback_ph: launch.info.clawback_ph,
+                start_time: launch.info.last_payment_time,
+                end_time: launch.info.end_time,
+            },
+            claimable_now: latest.as_ref().map_or(0, |latest| {
+                amount_to_be_paid(&latest.info, latest.coin.amount, timestamp)
+            }),
+            fully_vested_at: (status != StreamStatus::ClawedBack).then_some(launch.info.end_time),
+            claims,
+            latest,
+            status,
+            timestamp,
+        }
+    }
+
+    pub fn claimed_amount(&self) -> u64 {
+        self.claims.iter().map(|claim| claim.amount).sum()
+    }
+}
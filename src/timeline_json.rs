@@ -0,0 +1,96 @@
+use chia_wallet_sdk::utils::Address;
+use serde::Serialize;
+use streaming::{
+    state_token::to_state_token,
+    stream_id::StreamId,
+    timeline::{StreamStatus, StreamTimeline},
+};
+
+use crate::CliError;
+
+// `view --json` output: a `StreamTimeline` with addresses instead of puzzle hashes and hex
+// coin ids. All amounts are in mojos.
+
+#[derive(Debug, Serialize)]
+pub struct StreamTimelineJson {
+    pub stream_id: String,
+    /// "active", "finished" or "clawed_back"
+    pub status: String,
+    pub asset_id: String,
+    pub amount: u64,
+    pub recipient: String,
+    pub clawback_address: Option<String>,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub claims: Vec<StreamClaimJson>,
+    pub claimed_amount: u64,
+    pub latest_coin_id: Option<String>,
+    pub remaining_amount: Option<u64>,
+    pub claimable_now: u64,
+    pub fully_vested_at: Option<u64>,
+    pub state_token: Option<String>,
+    /// Timestamp of the latest block when the timeline was assembled
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamClaimJson {
+    pub coin_id: String,
+    pub spent_block_index: u32,
+    pub timestamp: u64,
+    pub amount: u64,
+    pub clawback: bool,
+}
+
+impl StreamTimelineJson {
+    pub fn new(
+        timeline: &StreamTimeline,
+        stream_prefix: &str,
+        prefix: &str,
+        state_token_prefix: &str,
+    ) -> Result<Self, CliError> {
+        let params = &timeline.parameters;
+
+        Ok(Self {
+            stream_id: StreamId(timeline.stream_id).encode(stream_prefix)?,
+            status: match timeline.status {
+                StreamStatus::Active => "active",
+                StreamStatus::Finished => "finished",
+                StreamStatus::ClawedBack => "clawed_back",
+            }
+            .to_string(),
+            asset_id: hex::encode(timeline.asset_id.to_vec()),
+            amount: timeline.amount,
+            recipient: Address::new(params.recipient, prefix.to_string()).encode()?,
+            clawback_address: params
+                .clawback_ph
+                .map(|clawback_ph| Address::new(clawback_ph, prefix.to_string()).encode())
+                .transpose()?,
+            start_time: params.start_time,
+            end_time: params.end_time,
+            claims: timeline
+                .claims
+                .iter()
+                .map(|claim| StreamClaimJson {
+                    coin_id: hex::encode(claim.coin_id.to_vec()),
+                    spent_block_index: claim.spent_block_index,
+                    timestamp: claim.timestamp,
+                    amount: claim.amount,
+                    clawback: claim.clawback,
+                })
+                .collect(),
+            claimed_amount: timeline.claimed_amount(),
+            latest_coin_id: timeline
+                .latest
+                .map(|latest| hex::encode(latest.coin.coin_id().to_vec())),
+            remaining_amount: timeline.latest.map(|latest| latest.coin.amount),
+            claimable_now: timeline.claimable_now,
+            fully_vested_at: timeline.fully_vested_at,
+            state_token: timeline
+                .latest
+                .map(|latest| to_state_token(&latest, state_token_prefix))
+                .transpose()?,
+            timestamp: timeline.timestamp,
+        })
+    }
+}
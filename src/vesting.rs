@@ -0,0 +1,432 @@
+use chia::puzzles::cat::CatArgs;
+use chia_protocol::Coin;
+use chia_wallet_sdk::driver::{StreamPuzzle2ndCurryArgs, StreamedCat, StreamingPuzzleInfo};
+
+use crate::error::{Result, StreamingError};
+
+/// Outcome of a single simulated spend of a streaming coin.
+#[derive(Debug, Clone, Copy)]
+pub struct SimulatedSpend {
+    pub payment_time: u64,
+    pub clawback: bool,
+    pub paid: u64,
+    pub returned: u64,
+    pub remaining: u64,
+}
+
+// All intermediate products are computed as u128: `my_amount * elapsed` is at most
+// u64::MAX * u64::MAX, which always fits. The results never exceed `my_amount`, so
+// any u64 amount and any u64 timestamps are supported.
+
+/// Amount the puzzle requires to be paid out when spending a coin of `my_amount` at
+/// `payment_time` (floor of `my_amount * elapsed / window`, same as the puzzle).
+/// `payment_time` is clamped to the [last_payment_time, end_time] window.
+pub fn amount_to_be_paid(info: &StreamingPuzzleInfo, my_amount: u64, payment_time: u64) -> u64 {
+    if info.end_time <= info.last_payment_time {
+        return my_amount;
+    }
+
+    let payment_time = payment_time.clamp(info.last_payment_time, info.end_time);
+    let elapsed = (payment_time - info.last_payment_time) as u128;
+    let window = (info.end_time - info.last_payment_time) as u128;
+
+    (my_amount as u128 * elapsed / window) as u64
+}
+
+/// Amount returned to the clawback address if the stream is clawed back at `payment_time`.
+pub fn clawback_return_amount(
+    info: &StreamingPuzzleInfo,
+    my_amount: u64,
+    payment_time: u64,
+) -> u64 {
+    my_amount - amount_to_be_paid(info, my_amount, payment_time)
+}
+
+/// Smallest `payment_time` in [last_payment_time, end_time] for which the puzzle pays out
+/// at least `desired`. Returns `None` if `desired` exceeds `my_amount`.
+pub fn payment_time_for_amount(
+    info: &StreamingPuzzleInfo,
+    my_amount: u64,
+    desired: u64,
+) -> Option<u64> {
+    if desired > my_amount {
+        return None;
+    }
+
+    if desired == 0 || info.end_time <= info.last_payment_time {
+        return Some(info.last_payment_time);
+    }
+
+    // to_pay = floor(my_amount * elapsed / window) >= desired
+    // <=> my_amount * elapsed >= desired * window
+    // <=> elapsed >= ceil(desired * window / my_amount)
+    let window = (info.end_time - info.last_payment_time) as u128;
+    let elapsed = (desired as u128 * window).div_ceil(my_amount as u128) as u64;
+
+    Some(info.last_payment_time + elapsed)
+}
+
+/// Whether a coin of `my_amount` is frozen until `end_time`: floor rounding makes every claim
+/// before then pay out 0, so the dust can only be claimed once the stream has ended.
+pub fn is_frozen_until_end(info: &StreamingPuzzleInfo, my_amount: u64) -> bool {
+    my_amount > 0
+        && payment_time_for_amount(info, my_amount, 1)
+            .is_some_and(|payment_time| payment_time >= info.end_time)
+}
+
+/// Streaming coin (and its info) that a claim at `payment_time` recreates, or `None` if the
+/// claim pays out everything that's left.
+pub fn child_after_claim(
+    stream: &StreamedCat,
+    payment_time: u64,
+) -> Option<(Coin, StreamingPuzzleInfo)> {
+    let remaining =
+        stream.coin.amount - amount_to_be_paid(&stream.info, stream.coin.amount, payment_time);
+    if remaining == 0 {
+        return None;
+    }
+
+    let info = StreamingPuzzleInfo::new(
+        stream.info.recipient,
+        stream.info.clawback_ph,
+        stream.info.end_time,
+        payment_time,
+    );
+    let inner_puzzle_hash = StreamPuzzle2ndCurryArgs::curry_tree_hash(
+        info.recipient,
+        info.clawback_ph,
+        info.end_time,
+        info.last_payment_time,
+    );
+    let puzzle_hash = CatArgs::curry_tree_hash(stream.asset_id, inner_puzzle_hash);
+
+    Some((
+        Coin::new(stream.coin.coin_id(), puzzle_hash.into(), remaining),
+        info,
+    ))
+}
+
+/// CAT coin a spend at `payment_time` pays to the recipient, or `None` if nothing is paid.
+pub fn payout_coin(stream: &StreamedCat, payment_time: u64) -> Option<Coin> {
+    let to_pay = amount_to_be_paid(&stream.info, stream.coin.amount, payment_time);
+    if to_pay == 0 {
+        return None;
+    }
+
+    Some(Coin::new(
+        stream.coin.coin_id(),
+        CatArgs::curry_tree_hash(stream.asset_id, stream.info.recipient.into()).into(),
+        to_pay,
+    ))
+}
+
+/// Replays a sequence of claims (in order) against a stream holding `amount`, mirroring how
+/// each spend recreates the coin with `last_payment_time = payment_time`. If `clawback_at`
+/// is set, the stream is clawed back at that time after the claims.
+pub fn simulate_stream(
+    info: &StreamingPuzzleInfo,
+    amount: u64,
+    claim_times: &[u64],
+    clawback_at: Option<u64>,
+) -> Vec<SimulatedSpend> {
+    let mut last_payment_time = info.last_payment_time;
+    let mut remaining = amount;
+    let mut spends = Vec::with_capacity(claim_times.len() + 1);
+
+    let payment_times = claim_times
+        .iter()
+        .map(|time| (*time, false))
+        .chain(clawback_at.map(|time| (time, true)));
+    for (payment_time, clawback) in payment_times {
+        if remaining == 0 {
+            break;
+        }
+
+        let current_info = StreamingPuzzleInfo::new(
+            info.recipient,
+            info.clawback_ph,
+            info.end_time,
+            last_payment_time,
+        );
+        let paid = amount_to_be_paid(&current_info, remaining, payment_time);
+        let returned = if clawback { remaining - paid } else { 0 };
+        remaining -= paid + returned;
+        last_payment_time = payment_time;
+
+        spends.push(SimulatedSpend {
+            payment_time,
+            clawback,
+            paid,
+            returned,
+            remaining,
+        });
+    }
+
+    spends
+}
+
+/// Approximate mainnet average time between transaction blocks, in seconds.
+pub const AVERAGE_BLOCK_TIME: u64 = 52;
+
+/// How a stream vests in terms of blocks instead of seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockProjection {
+    /// Average amount that vests per block
+    pub amount_per_block: u64,
+    /// Approximate number of blocks until the stream is fully vested
+    pub blocks_remaining: u64,
+}
+
+/// Projects the vesting of a coin of `my_amount` onto blocks that are `block_time` seconds
+/// apart on average, as seen at `now`. The rate is constant over the whole window, so it's
+/// the same for every block until `end_time`.
+pub fn project_per_block(
+    info: &StreamingPuzzleInfo,
+    my_amount: u64,
+    now: u64,
+    block_time: u64,
+) -> BlockProjection {
+    let block_time = block_time.max(1);
+    if info.end_time <= info.last_payment_time {
+        return BlockProjection {
+            amount_per_block: my_amount,
+            blocks_remaining: 0,
+        };
+    }
+
+    let window = (info.end_time - info.last_payment_time) as u128;
+    let amount_per_block = (my_amount as u128 * block_time as u128 / window).min(my_amount as u128);
+    let remaining_time = info
+        .end_time
+        .saturating_sub(now.max(info.last_payment_time));
+
+    BlockProjection {
+        amount_per_block: amount_per_block as u64,
+        blocks_remaining: remaining_time.div_ceil(block_time),
+    }
+}
+
+// Start timestamps further in the past than this are almost certainly typos
+const MAX_START_TIME_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// Sanity checks for a new stream's window. `now` is the current unix timestamp.
+pub fn validate_time_window(start_timestamp: u64, end_timestamp: u64, now: u64) -> Result<()> {
+    if start_timestamp == 0 || end_timestamp == 0 {
+        return Err(StreamingError::TimeWindowInvalid(
+            "timestamps must be non-zero".to_string(),
+        ));
+    }
+
+    if end_timestamp <= start_timestamp {
+        return Err(StreamingError::TimeWindowInvalid(
+            "end timestamp must be after start timestamp".to_string(),
+        ));
+    }
+
+    if start_timestamp.saturating_add(MAX_START_TIME_AGE) < now {
+        return Err(StreamingError::TimeWindowInvalid(
+            "start timestamp is more than a year in the past".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chia_protocol::Bytes32;
+
+    use super::*;
+
+    const YEAR: u64 = 365 * 24 * 60 * 60;
+
+    fn info(last_payment_time: u64, end_time: u64) -> StreamingPuzzleInfo {
+        StreamingPuzzleInfo::new(Bytes32::new([1; 32]), None, end_time, last_payment_time)
+    }
+
+    #[test]
+    fn amount_to_be_paid_max_amount() {
+        let info = info(1_000, 1_000 + 10 * YEAR);
+
+        assert_eq!(amount_to_be_paid(&info, u64::MAX, 1_000), 0);
+        assert_eq!(
+            amount_to_be_paid(&info, u64::MAX, 1_000 + 5 * YEAR),
+            u64::MAX / 2
+        );
+        assert_eq!(
+            amount_to_be_paid(&info, u64::MAX, 1_000 + 10 * YEAR),
+            u64::MAX
+        );
+        assert_eq!(amount_to_be_paid(&info, u64::MAX, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn amount_to_be_paid_max_window() {
+        let info = info(0, u64::MAX);
+
+        assert_eq!(
+            amount_to_be_paid(&info, u64::MAX, u64::MAX - 1),
+            u64::MAX - 1
+        );
+        assert_eq!(amount_to_be_paid(&info, u64::MAX, u64::MAX), u64::MAX);
+        assert_eq!(amount_to_be_paid(&info, 1_000, u64::MAX / 2), 499);
+    }
+
+    #[test]
+    fn amount_to_be_paid_multi_year() {
+        let start = 1_700_000_000;
+        let info = info(start, start + 4 * YEAR);
+        let amount = 4_000_000_000_000;
+
+        for year in 0..=4 {
+            assert_eq!(
+                amount_to_be_paid(&info, amount, start + year * YEAR),
+                year * 1_000_000_000_000
+            );
+        }
+        assert_eq!(
+            amount_to_be_paid(&info, amount, start + YEAR - 1),
+            999_999_968_290
+        );
+    }
+
+    #[test]
+    fn payment_time_for_amount_exact_multiple() {
+        let info = info(1_000, 2_000);
+
+        // 250 * 1_000 / 500 divides exactly, so no rounding up
+        assert_eq!(payment_time_for_amount(&info, 500, 250), Some(1_500));
+        assert_eq!(amount_to_be_paid(&info, 500, 1_500), 250);
+        assert_eq!(amount_to_be_paid(&info, 500, 1_499), 249);
+    }
+
+    #[test]
+    fn payment_time_for_amount_rounds_up() {
+        let info = info(1_000, 2_000);
+
+        // 1 * 1_000 / 300 = 3.33, so the first second that pays out 1 is 1_004
+        assert_eq!(payment_time_for_amount(&info, 300, 1), Some(1_004));
+        assert_eq!(amount_to_be_paid(&info, 300, 1_004), 1);
+        assert_eq!(amount_to_be_paid(&info, 300, 1_003), 0);
+    }
+
+    #[test]
+    fn payment_time_for_amount_whole_remaining() {
+        let info = info(1_000, 2_000);
+
+        assert_eq!(payment_time_for_amount(&info, 300, 300), Some(2_000));
+        assert_eq!(amount_to_be_paid(&info, 300, 1_999), 299);
+        assert_eq!(payment_time_for_amount(&info, 300, 301), None);
+    }
+
+    #[test]
+    fn payment_time_for_amount_zero() {
+        let info = info(1_000, 2_000);
+
+        assert_eq!(payment_time_for_amount(&info, 300, 0), Some(1_000));
+        assert_eq!(payment_time_for_amount(&info, 0, 0), Some(1_000));
+    }
+
+    #[test]
+    fn validate_time_window_bounds() {
+        let now = 1_700_000_000;
+
+        assert!(validate_time_window(now, now + 1, now).is_ok());
+        assert!(validate_time_window(now - YEAR, now + YEAR, now).is_ok());
+
+        for (start, end) in [
+            (now, now),
+            (now + 1, now),
+            (now, 0),
+            (0, now),
+            (now - YEAR - 1, now),
+        ] {
+            assert!(matches!(
+                validate_time_window(start, end, now),
+                Err(StreamingError::TimeWindowInvalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn amount_to_be_paid_is_monotonic() {
+        let info = info(1_000, 1_997);
+
+        for amount in [0, 1, 7, 996, 997, 998, 1_000_003, u64::MAX] {
+            let mut previous = 0;
+            for payment_time in 900..2_100 {
+                let paid = amount_to_be_paid(&info, amount, payment_time);
+                assert!(paid >= previous);
+                assert!(paid <= amount);
+                previous = paid;
+            }
+            assert_eq!(previous, amount);
+        }
+    }
+
+    #[test]
+    fn payment_time_for_amount_is_earliest() {
+        let info = info(1_000, 1_997);
+
+        for amount in [1, 7, 996, 997, 998, 1_000_003] {
+            for desired in [1, amount / 3, amount / 2, amount - 1, amount] {
+                let time = payment_time_for_amount(&info, amount, desired).unwrap();
+                assert!(amount_to_be_paid(&info, amount, time) >= desired);
+                if time > info.last_payment_time {
+                    assert!(amount_to_be_paid(&info, amount, time - 1) < desired);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simulate_stream_conserves_amount() {
+        let info = info(1_000, 1_000 + 3 * YEAR);
+        let claim_times = [
+            1_000 + 1,
+            1_000 + YEAR / 7,
+            1_000 + YEAR,
+            1_000 + 2 * YEAR + 13,
+        ];
+
+        for amount in [1, 999, 1_000_003, 4_000_000_000_000, u64::MAX] {
+            for clawback_at in [None, Some(1_000 + 2 * YEAR + 500), Some(1_000 + 3 * YEAR)] {
+                let spends = simulate_stream(&info, amount, &claim_times, clawback_at);
+
+                let mut left = amount;
+                for spend in &spends {
+                    assert_eq!(spend.paid + spend.returned + spend.remaining, left);
+                    left = spend.remaining;
+                }
+
+                let paid: u64 = spends.iter().map(|spend| spend.paid).sum();
+                let returned: u64 = spends.iter().map(|spend| spend.returned).sum();
+                assert_eq!(paid + returned + left, amount);
+                if clawback_at.is_some() {
+                    assert_eq!(left, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clawback_split() {
+        let info = info(1_000, 2_000);
+        let amount = 1_000_001;
+
+        // (payment time, paid to the recipient, returned to the clawback address)
+        for (payment_time, paid, returned) in [
+            (1_000, 0, 1_000_001),
+            (1_500, 500_000, 500_001),
+            (1_999, 999_000, 1_001),
+            (2_000, 1_000_001, 0),
+            (2_500, 1_000_001, 0),
+        ] {
+            assert_eq!(amount_to_be_paid(&info, amount, payment_time), paid);
+            assert_eq!(
+                clawback_return_amount(&info, amount, payment_time),
+                returned
+            );
+        }
+    }
+}